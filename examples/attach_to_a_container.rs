@@ -12,15 +12,21 @@ async fn main() -> Result<(), Error> {
     let x = d.container("zen_dubinsky");
     // Initialize options for attaching
     let mut opts = AttachOpts::new();
-    // stream the stdout
-    opts.stream(true).stdout(true);
+    // replay previous logs and stream stdout and stderr
+    opts.stream(true)
+        .logs(true)
+        .stdout(true)
+        .stderr(true)
+        .detach_keys("ctrl-p,ctrl-q");
 
     match x.attach(&opts).await {
         Ok(mut upgraded) => {
             // Read the upgraded stream byte by byte
             loop {
                 let mut buf = [0; 1];
-                upgraded.read(&mut buf).await?;
+                if upgraded.read(&mut buf).await? == 0 {
+                    break;
+                }
                 print!("{}", buf[0] as char);
             }
         }
@@ -19,13 +19,14 @@ async fn main() -> Result<(), Error> {
     let mut opts = ContainerBuilderOpts::new();
     opts.image("ubuntu:latest")
         .tty(true)
-        .shell(&["/bin/bash".into()])
+        .shell(&["/bin/bash"])
         .cmd(&["/bin/echo", "test"])
         .labels(&labels)
         .env(&["HTTP_PROXY=http://proxy.domain.com"]);
 
     // Create a container
-    c.create("container_name", &opts).await?;
+    let container = c.create("container_name", &opts).await?;
+    println!("created container {}", container.id);
 
     Ok(())
 }
@@ -21,22 +21,39 @@
 //! }
 //! ```
 extern crate base64;
+use crate::error::DockerApiError;
+use crate::image_ref::ImageRef;
+use crate::logging::*;
 use crate::opts::*;
 use crate::result::*;
-use crate::{Docker, Msg};
+use crate::{Docker, DockerMessage};
 use failure::Error;
-use futures::stream::StreamExt;
-use hyper::{body::to_bytes, Body, Method};
-use log::*;
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt, TryStreamExt};
+use hyper::{
+    body::{to_bytes, Bytes},
+    Body, Method,
+};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
 use std::path::Path;
 use std::str;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tokio::task;
+// How often wait_healthy() inspects the container
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(500);
 macro_rules! err_msg {
-    ($t: ident, $e: expr) => {
-        match serde_json::from_slice::<Msg>($t.as_ref()) {
-            Ok(m) => Err(format_err!("{} - {}", $e, m.msg())),
-            _ => Err(format_err!("{}", $e)),
-        }
+    ($t: ident, $status: expr, $e: expr) => {
+        Err(Error::from(DockerApiError::new(
+            $status,
+            $e,
+            serde_json::from_slice::<DockerMessage>($t.as_ref())
+                .ok()
+                .map(DockerMessage::msg),
+        )))
     };
 }
 macro_rules! post_container {
@@ -52,13 +69,85 @@ macro_rules! post_container {
 
         match status {
             204 => Ok(()),
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "internal server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }};
 }
 
+// Parses a stream of json objects from the response body.
+// A single object can be split between multiple chunks so incomplete data is buffered.
+pub(crate) fn json_stream<T: DeserializeOwned>(body: Body) -> impl Stream<Item = Result<T, Error>> {
+    let mut buf = Vec::new();
+    body.map(move |chunk| {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return vec![Err(e.into())],
+        };
+        buf.extend_from_slice(&chunk);
+        let mut items = Vec::new();
+        let mut objects = serde_json::Deserializer::from_slice(&buf).into_iter::<T>();
+        let consumed = loop {
+            match objects.next() {
+                Some(Ok(item)) => items.push(Ok(item)),
+                Some(Err(e)) if e.is_eof() => break objects.byte_offset(),
+                Some(Err(e)) => {
+                    items.push(Err(e.into()));
+                    break buf.len();
+                }
+                None => break buf.len(),
+            }
+        };
+        buf.drain(..consumed);
+        items
+    })
+    .flat_map(stream::iter)
+}
+
+// Parses a stream of progress messages from the response body.
+// Errors that occur after the response started are sent as {"error": "..."} messages.
+fn progress_stream<T: DeserializeOwned>(body: Body) -> impl Stream<Item = Result<T, Error>> {
+    json_stream::<Value>(body).map(|msg| {
+        let msg = msg?;
+        match msg.get("error") {
+            Some(e) => Err(format_err!("{}", e.as_str().unwrap_or_default())),
+            None => Ok(serde_json::from_value(msg)?),
+        }
+    })
+}
+
+// Splits multiplexed stdout and stderr of a container without a TTY into frames.
+// Each frame starts with an 8 byte header [STREAM_TYPE, 0, 0, 0, SIZE1, SIZE2, SIZE3, SIZE4]
+// where size is encoded as big endian u32.
+fn demux_stream(body: Body) -> impl Stream<Item = Result<LogFrame, Error>> {
+    let mut buf = Vec::new();
+    body.map(move |chunk| {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => return vec![Err(e.into())],
+        };
+        buf.extend_from_slice(&chunk);
+        let mut frames = Vec::new();
+        while buf.len() >= 8 {
+            let size = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+            if buf.len() < 8 + size {
+                break;
+            }
+            let data = buf[8..8 + size].to_vec();
+            frames.push(match buf[0] {
+                0 => Ok(LogFrame::Stdin(data)),
+                1 => Ok(LogFrame::Stdout(data)),
+                2 => Ok(LogFrame::Stderr(data)),
+                other => Err(format_err!("invalid stream type {} in frame header", other)),
+            });
+            buf.drain(..8 + size);
+        }
+        frames
+    })
+    .flat_map(stream::iter)
+}
+
 // * Containers start *
 
 /// Api wrapper for a single container
@@ -84,18 +173,20 @@ macro_rules! post_container {
 #[derive(Debug)]
 pub struct Container<'d> {
     docker: &'d Docker,
-    pub id: String,
+    pub id: ContainerId,
 }
 impl<'d> Container<'d> {
     /// new API interface for containers
-    pub fn new<S: Into<String>>(docker: &'d Docker, id: S) -> Container<'d> {
+    pub fn new<I: Into<ContainerId>>(docker: &'d Docker, id: I) -> Container<'d> {
         Container {
             docker,
             id: id.into(),
         }
     }
-    /// Starts the container
-    pub async fn start(&self) -> Result<(), Error> {
+    /// Starts the container  
+    /// Returns `StartResult::AlreadyRunning` if the container was already started.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn start(&self) -> Result<StartResult, Error> {
         let res = self
             .docker
             .req(
@@ -111,15 +202,17 @@ impl<'d> Container<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            204 => Ok(()),
-            304 => err_msg!(text, "container already started"),
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            204 => Ok(StartResult::Started),
+            304 => Ok(StartResult::AlreadyRunning),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Stops the container
-    pub async fn stop(&self) -> Result<(), Error> {
+    /// Stops the container  
+    /// Returns `StopResult::AlreadyStopped` if the container was already stopped.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn stop(&self) -> Result<StopResult, Error> {
         let res = self
             .docker
             .req(
@@ -135,15 +228,16 @@ impl<'d> Container<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            204 => Ok(()),
-            304 => err_msg!(text, "container already stopped"),
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            204 => Ok(StopResult::Stopped),
+            304 => Ok(StopResult::AlreadyStopped),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Inspect a container
     /// Return low-level information about a container.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn inspect(&self) -> Result<ContainerInspect, Error> {
         let res = self
             .docker
@@ -164,12 +258,13 @@ impl<'d> Container<'d> {
                 let data: ContainerInspect = serde_json::from_slice(&text)?;
                 Ok(data)
             }
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Restarts the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn restart(&self) -> Result<(), Error> {
         Ok(post_container!(
             format!("/containers/{}/restart", self.id),
@@ -177,6 +272,7 @@ impl<'d> Container<'d> {
         )?)
     }
     /// Kills the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn kill(&self) -> Result<(), Error> {
         Ok(post_container!(
             format!("/containers/{}/kill", self.id),
@@ -184,6 +280,7 @@ impl<'d> Container<'d> {
         )?)
     }
     /// Unpauses the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn unpause(&self) -> Result<(), Error> {
         Ok(post_container!(
             format!("/containers/{}/unpause", self.id),
@@ -191,6 +288,7 @@ impl<'d> Container<'d> {
         )?)
     }
     /// Pauses the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn pause(&self) -> Result<(), Error> {
         Ok(post_container!(
             format!("/containers/{}/pause", self.id),
@@ -198,6 +296,7 @@ impl<'d> Container<'d> {
         )?)
     }
     /// Rename container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn rename(&mut self, new_name: &str) -> Result<(), Error> {
         let res = self
             .docker
@@ -215,16 +314,17 @@ impl<'d> Container<'d> {
         trace!("{}", str::from_utf8(&text)?);
         match status {
             204 => {
-                self.id = new_name.to_string();
+                self.id = new_name.into();
                 Ok(())
             }
-            404 => err_msg!(text, "no such container"),
-            409 => err_msg!(text, "name already in use"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            409 => err_msg!(text, 409, "name already in use"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Remove a container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn remove(&self, opts: &RmContainerOpts) -> Result<(), Error> {
         let res = self
             .docker
@@ -242,18 +342,52 @@ impl<'d> Container<'d> {
         trace!("{}", str::from_utf8(&text)?);
         match status {
             204 => Ok(()),
-            404 => err_msg!(text, "no such container"),
-            409 => err_msg!(text, "name already in use"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            409 => err_msg!(text, 409, "name already in use"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Work in progress...
-    pub async fn logs(&self, _opts: &ContainerLogsOpts) -> Result<String, Error> {
-        unimplemented!()
+    /// Get stdout and stderr logs from the container  
+    /// If the container was created without a TTY the output is split into stdout and stderr frames,
+    /// otherwise all of the output is returned as stdout.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn logs(
+        &self,
+        opts: &ContainerLogsOpts,
+    ) -> Result<impl Stream<Item = Result<LogFrame, Error>>, Error> {
+        let tty = self.inspect().await?.config.tty;
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/containers/{}/logs", self.id),
+                Some(opts.to_query()?),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        match status {
+            200 if tty => Ok(res
+                .into_body()
+                .map(|chunk| Ok(LogFrame::Stdout(chunk?.to_vec())))
+                .left_stream()),
+            200 => Ok(demux_stream(res.into_body()).right_stream()),
+            other => {
+                let text = to_bytes(res.into_body()).await?;
+                trace!("{}", str::from_utf8(&text)?);
+                match other {
+                    404 => err_msg!(text, 404, "no such container"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
+                }
+            }
+        }
     }
     /// Get a tar archive of a resource in the filesystem of container id  
     /// Returns a tar archived path
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, p)))]
     pub async fn archive_path<P: AsRef<Path>>(&self, p: P) -> Result<Vec<u8>, Error> {
         let res = self
             .docker
@@ -269,14 +403,91 @@ impl<'d> Container<'d> {
         let text = to_bytes(res.into_body()).await?;
         match status {
             200 => Ok(text.to_vec()),
-            400 => err_msg!(text, "container or path does not exist"),
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            400 => err_msg!(text, 400, "container or path does not exist"),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Copy a file or directory at src_path in the container into the local directory dest_path
+    /// like `docker cp <container>:<src_path> <dest_path>` does. The directory is created if it doesn't exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self, src_path, dest_path))
+    )]
+    pub async fn copy_from<S, D>(&self, src_path: S, dest_path: D) -> Result<(), Error>
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let archive = self.archive_path(src_path).await?;
+        let dest = dest_path.as_ref().to_path_buf();
+        // Unpacking writes to disk so it's done on the blocking thread pool
+        task::spawn_blocking(move || tar::Archive::new(archive.as_slice()).unpack(dest)).await??;
+        Ok(())
+    }
+    /// Copy a local file or directory at src_path into the directory dest_container_path in the container
+    /// like `docker cp <src_path> <container>:<dest_container_path>` does.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self, src_path, dest_container_path))
+    )]
+    pub async fn copy_to<S, D>(&self, src_path: S, dest_container_path: D) -> Result<(), Error>
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let src = src_path.as_ref().to_path_buf();
+        // Reading the files is blocking so the archive is built on the blocking thread pool
+        let archive = task::spawn_blocking(move || {
+            let mut archive = tar::Builder::new(Vec::new());
+            match src.file_name() {
+                Some(name) if src.is_dir() => archive.append_dir_all(name, &src)?,
+                Some(name) => archive.append_path_with_name(&src, name)?,
+                None => archive.append_dir_all(".", &src)?,
+            }
+            archive.into_inner()
+        })
+        .await??;
+
+        let dest = dest_container_path.as_ref();
+        let mut opts = UploadArchiveOpts::new();
+        opts.path(
+            dest.to_str()
+                .ok_or_else(|| format_err!("invalid path {}", dest.display()))?,
+        );
+        self.upload_archive(&archive, &opts).await
+    }
+    /// Export the contents of container id as a tar archive  
+    /// The archive is streamed in chunks as it's received so it never has to fit in memory.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn export_stream(&self) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/containers/{}/export", self.id),
+                None,
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        match status {
+            200 => Ok(res.into_body().map(|chunk| chunk.map_err(Error::from))),
+            other => {
+                let text = to_bytes(res.into_body()).await?;
+                match other {
+                    404 => err_msg!(text, 404, "no such container"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
+                }
+            }
         }
     }
     /// Upload a tar archive to be extracted to a path in the filesystem of container id.  
     /// The input file must be a tar archive compressed with one of the following algorithms: identity (no compression), gzip, bzip2, xz.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, archive)))]
     pub async fn upload_archive(
         &self,
         archive: &[u8],
@@ -289,7 +500,7 @@ impl<'d> Container<'d> {
                 format!("/containers/{}/archive", self.id),
                 Some(opts.to_query()?),
                 Body::from(archive.to_vec()),
-                None,
+                Some(vec![("Content-Length", archive.len().to_string())]),
             )
             .await?;
         let status = res.status().as_u16();
@@ -297,18 +508,20 @@ impl<'d> Container<'d> {
         trace!("{}", str::from_utf8(&text)?);
         match status {
             200 => Ok(()),
-            400 => err_msg!(text, "container or path does not exist"),
+            400 => err_msg!(text, 400, "container or path does not exist"),
             403 => err_msg!(
                 text,
+                403,
                 "permission denied, the volume or container rootfs is marked as read-only"
             ),
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Get information about files in a container  
     /// A response header X-Docker-Container-Path-Stat is return containing a base64 - encoded JSON object with some filesystem header information about the path.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, path)))]
     pub async fn file_info<P: AsRef<Path>>(&self, path: P) -> Result<FileInfo, Error> {
         let res = self
             .docker
@@ -336,16 +549,17 @@ impl<'d> Container<'d> {
                 let text = to_bytes(res.into_body()).await?;
                 trace!("{}", str::from_utf8(&text)?);
                 match other {
-                    400 => err_msg!(text, "bad parameter"),
-                    404 => err_msg!(text, "container or path does not exist"),
-                    500 => err_msg!(text, "server error"),
-                    _ => err_msg!(text, ""),
+                    400 => err_msg!(text, 400, "bad parameter"),
+                    404 => err_msg!(text, 404, "no such container or path"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
                 }
             }
         }
     }
     /// List processes running inside a container  
     /// On Unix systems, this is done by running the ps command. This endpoint is not supported on Windows.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, ps_args)))]
     pub async fn ps<S: AsRef<str>>(&self, ps_args: S) -> Result<Vec<Process>, Error> {
         let res = self
             .docker
@@ -366,15 +580,190 @@ impl<'d> Container<'d> {
                 Ok(data
                     .Processes
                     .iter()
-                    .map(|p| Process::new(&data.Titles, &p))
+                    .map(|p| Process::new(&data.Titles, p))
                     .collect())
             }
-            404 => err_msg!(text, "no such container"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
+    /// Block until the health check of the container passes  
+    /// Fails if the container has no health check or it isn't healthy before the timeout.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn wait_healthy(&self, timeout: Duration) -> Result<(), Error> {
+        let start = Instant::now();
+        loop {
+            let inspect = self.inspect().await?;
+            let status = match inspect.health_status() {
+                Some("healthy") => return Ok(()),
+                Some(status) => status,
+                None => return Err(format_err!("container {} has no health check", self.id)),
+            };
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(format_err!(
+                    "container {} not healthy after {:?} - last health status: {}",
+                    self.id,
+                    timeout,
+                    status
+                ));
+            }
+            tokio::time::delay_for(HEALTH_POLL_INTERVAL.min(timeout - elapsed)).await;
+        }
+    }
+    /// Block until the container reaches the condition  
+    /// Returns the exit code of the container.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn wait(&self, condition: WaitCondition) -> Result<WaitResponse, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                format!("/containers/{}/wait", self.id),
+                Some(format!("condition={}", condition)),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Get a single snapshot of resource usage statistics of the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn stats(&self) -> Result<ContainerStats, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/containers/{}/stats", self.id),
+                Some("stream=false".into()),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Create a checkpoint of the container  
+    /// Requires the daemon to run with experimental features and CRIU installed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn create_checkpoint(&self, opts: &CheckpointCreateOpts) -> Result<(), Error> {
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                format!("/containers/{}/checkpoints", self.id),
+                None,
+                Body::from(serde_json::to_string(opts.opts())?),
+                Some(vec![("Content-type", "application/json".into())]),
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            201 => Ok(()),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// List checkpoints of the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn list_checkpoints(&self) -> Result<Vec<CheckpointData>, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/containers/{}/checkpoints", self.id),
+                None,
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Delete a checkpoint of the container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn delete_checkpoint(&self, checkpoint_id: &str) -> Result<(), Error> {
+        let res = self
+            .docker
+            .req(
+                Method::DELETE,
+                format!("/containers/{}/checkpoints/{}", self.id, checkpoint_id),
+                None,
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            204 => Ok(()),
+            404 => err_msg!(text, 404, "no such checkpoint"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Get changes on the filesystem of the container  
+    /// Returns paths that were modified, added or deleted since the container was created.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn changes(&self) -> Result<Vec<FilesystemChange>, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/containers/{}/changes", self.id),
+                None,
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            // The daemon returns null if nothing changed
+            200 => Ok(
+                serde_json::from_slice::<Option<Vec<FilesystemChange>>>(&text)?.unwrap_or_default(),
+            ),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// List processes running inside a container with default ps arguments (-ef)  
+    /// This endpoint is not supported on Windows.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn top(&self) -> Result<Vec<Process>, Error> {
+        self.ps("").await
+    }
     /// Attach to a container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn attach(&self, opts: &AttachOpts) -> Result<hyper::upgrade::Upgraded, Error> {
         let res = self
             .docker
@@ -399,52 +788,106 @@ impl<'d> Container<'d> {
                 let text = to_bytes(res.into_body()).await?;
                 trace!("{}", str::from_utf8(&text)?);
                 match other {
-                    400 => err_msg!(text, "bad parameter"),
-                    404 => err_msg!(text, "no such container"),
-                    500 => err_msg!(text, "server error"),
-                    _ => err_msg!(text, ""),
+                    400 => err_msg!(text, 400, "bad parameter"),
+                    404 => err_msg!(text, 404, "no such container"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
                 }
             }
         }
     }
     /// Exec a command
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn exec(&self, opts: &ExecOpts) -> Result<CmdOut, Error> {
         let exec_id = self.create_exec_instance(opts).await?;
         let exec_id = exec_id.trim_matches('"');
         Ok(CmdOut {
-            out: self.start_exec_instance(&exec_id, opts).await?,
-            info: self.docker.exec_inspect(&exec_id).await?,
+            out: self.start_exec_instance(exec_id, opts).await?,
+            info: self.docker.exec_inspect(exec_id).await?,
+        })
+    }
+    /// Exec a command in the working directory dir attaching stdout and stderr
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn exec_in_dir(&self, cmd: &[&str], dir: &str) -> Result<CmdOut, Error> {
+        let mut opts = ExecOpts::new();
+        opts.attach_stdout(true)
+            .attach_stderr(true)
+            .cmd(cmd)
+            .working_dir(dir);
+        self.exec(&opts).await
+    }
+    /// Exec a command and collect its stdout and stderr separately  
+    /// If tty is enabled in opts the streams can't be distinguished so all of the output is returned as stdout.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn exec_split(&self, opts: &ExecOpts) -> Result<ExecOutput, Error> {
+        let exec_id = self.create_exec_instance(opts).await?;
+        let exec_id = exec_id.trim_matches('"');
+        let body = self.start_exec(exec_id, opts).await?;
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        if opts._tty() {
+            stdout = to_bytes(body).await?.to_vec();
+        } else {
+            let mut frames = demux_stream(body);
+            while let Some(frame) = frames.next().await {
+                match frame? {
+                    LogFrame::Stdout(data) => stdout.extend(data),
+                    LogFrame::Stderr(data) => stderr.extend(data),
+                    LogFrame::Stdin(_) => {}
+                }
+            }
+        }
+        Ok(ExecOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            exit_code: self.docker.exec_inspect(exec_id).await?.exit_code,
         })
     }
+    /// Inspect an exec instance started in this container  
+    /// Returns its exit code and whether it's still running.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn inspect_exec(&self, exec_id: &str) -> Result<ExecInspect, Error> {
+        self.docker.exec_inspect(exec_id).await
+    }
     // Starts the exec instance
     async fn start_exec_instance(&self, id: &str, opts: &ExecOpts) -> Result<String, Error> {
+        let slice = to_bytes(self.start_exec(id, opts).await?).await?;
+        match str::from_utf8(&slice) {
+            Ok(text) => {
+                trace!("{}", text);
+                Ok(text.to_string())
+            }
+            Err(e) => {
+                error!("failed to parse text from exec {} - {}", &id, e);
+                Ok("".to_string())
+            }
+        }
+    }
+    // Starts the exec instance and returns the output body
+    async fn start_exec(&self, id: &str, opts: &ExecOpts) -> Result<Body, Error> {
         let res = self
             .docker
             .req(
                 Method::POST,
                 format!("/exec/{}/start", id),
                 None,
-                Body::from(serde_json::to_vec(opts.opts())?),
+                Body::from(serde_json::to_vec(&opts._start_body())?),
                 Some(vec![("Content-type", "application/json".into())]),
             )
             .await?;
 
         let status = res.status().as_u16();
-        let slice = to_bytes(res.into_body()).await?;
         match status {
-            200 => match str::from_utf8(&slice) {
-                Ok(text) => {
-                    trace!("{}", text);
-                    Ok(text.to_string())
-                }
-                Err(e) => {
-                    error!("failed to parse text from exec {} - {}", &id, e);
-                    Ok("".to_string())
+            200 => Ok(res.into_body()),
+            other => {
+                let slice = to_bytes(res.into_body()).await?;
+                trace!("{}", str::from_utf8(&slice)?);
+                match other {
+                    404 => err_msg!(slice, 404, "no such exec instance"),
+                    409 => err_msg!(slice, 409, "container is paused"),
+                    _ => err_msg!(slice, other, ""),
                 }
-            },
-            404 => err_msg!(slice, "no such exec instance"),
-            409 => err_msg!(slice, "container is paused"),
-            _ => err_msg!(slice, ""),
+            }
         }
     }
     // Returns Id of exec instance
@@ -473,10 +916,10 @@ impl<'d> Container<'d> {
                 }
                 _ => Err(format_err!("there was no field Id in the response body.")),
             },
-            404 => err_msg!(slice, "no such container"),
-            409 => err_msg!(slice, "container is paused"),
-            500 => err_msg!(slice, "server error"),
-            _ => err_msg!(slice, ""),
+            404 => err_msg!(slice, 404, "no such container"),
+            409 => err_msg!(slice, 409, "container is paused"),
+            500 => err_msg!(slice, 500, "server error"),
+            _ => err_msg!(slice, status, ""),
         }
     }
 }
@@ -487,11 +930,12 @@ pub struct Containers<'d> {
 }
 impl<'d> Containers<'d> {
     /// new API interface for containers
-    pub fn new(docker: &'d Docker) -> Containers {
+    pub fn new(docker: &'d Docker) -> Containers<'d> {
         Containers { docker }
     }
     /// List all containers
-    pub async fn list(&self, opts: &ListContainersOpts) -> Result<Vec<Container<'_>>, Error> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn list(&self, opts: &ListContainersOpts) -> Result<Vec<ContainerData>, Error> {
         let res = self
             .docker
             .req(
@@ -510,29 +954,60 @@ impl<'d> Containers<'d> {
             200 => {
                 let data: Vec<ContainerData> = serde_json::from_slice(&text)?;
                 debug!("{:?}", data);
-                let docker = self.docker;
-                Ok(data
-                    .iter()
-                    .map(|c| Container {
-                        docker,
-                        id: c.id.clone(),
-                    })
-                    .collect())
+                Ok(data)
             }
-            400 => err_msg!(text, "bad parameter"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            400 => err_msg!(text, 400, "bad parameter"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Create a container
-    pub async fn create(&self, name: &str, opts: &ContainerBuilderOpts) -> Result<(), Error> {
+    /// List containers and get a handle for each of them  
+    /// Useful when operations on listed containers are needed rather than their data.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn handles(&self, opts: &ListContainersOpts) -> Result<Vec<Container<'d>>, Error> {
+        Ok(self
+            .list(opts)
+            .await?
+            .iter()
+            .map(|data| Container::new(self.docker, &data.id))
+            .collect())
+    }
+    /// Run `f` concurrently on every container matching `opts`, f.e. to stop all of them.  
+    /// Returns the result of each call in the order they finished.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, f)))]
+    pub async fn for_each_concurrent<F, Fut>(
+        &self,
+        opts: &ListContainersOpts,
+        f: F,
+    ) -> Result<Vec<Result<(), Error>>, Error>
+    where
+        F: Fn(Container<'d>) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        Ok(self
+            .handles(opts)
+            .await?
+            .into_iter()
+            .map(f)
+            .collect::<FuturesUnordered<_>>()
+            .collect()
+            .await)
+    }
+    /// Create a container  
+    /// Returns a handle of the created container
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn create(
+        &self,
+        name: &str,
+        opts: &ContainerBuilderOpts,
+    ) -> Result<Container<'d>, Error> {
         let res = self
             .docker
             .req(
                 Method::POST,
                 "/containers/create".into(),
                 Some(format!("name={}", name)),
-                Body::from(serde_json::to_string(opts.opts())?),
+                Body::from(opts.to_body()?),
                 Some(vec![("Content-type", "application/json".into())]),
             )
             .await?;
@@ -540,12 +1015,42 @@ impl<'d> Containers<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            201 => Ok(()),
-            400 => err_msg!(text, "bad parameter"),
-            404 => err_msg!(text, "no such container"),
-            409 => err_msg!(text, "conflict"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            201 => {
+                let out: ContainerCreateOut = serde_json::from_slice(&text)?;
+                out.warnings
+                    .unwrap_or_default()
+                    .iter()
+                    .for_each(|w| warn!("{}", w));
+                Ok(Container::new(self.docker, out.id))
+            }
+            400 => err_msg!(text, 400, "bad parameter"),
+            404 => err_msg!(text, 404, "no such container"),
+            409 => err_msg!(text, 409, "conflict"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Delete stopped containers  
+    /// Filters like until or label can be used to limit what gets deleted.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn prune(&self, filters: &FilterBuilder) -> Result<ContainersPruned, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                "/containers/prune".into(),
+                Some(filters.to_query()?),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
 }
@@ -553,21 +1058,53 @@ impl<'d> Containers<'d> {
 
 // * Networks start *
 
+/// Api wrapper for a single network
+#[derive(Debug)]
+pub struct Network<'d> {
+    docker: &'d Docker,
+    pub id: String,
+}
+impl<'d> Network<'d> {
+    /// new API interface for a network
+    pub fn new<S: Into<String>>(docker: &'d Docker, id: S) -> Network<'d> {
+        Network {
+            docker,
+            id: id.into(),
+        }
+    }
+    /// Inspect the network
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn inspect(&self) -> Result<NetworkInspect, Error> {
+        Networks::new(self.docker).inspect(&self.id).await
+    }
+    /// Remove the network
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn remove(&self) -> Result<(), Error> {
+        Networks::new(self.docker).remove(&self.id).await
+    }
+}
+
 /// Api wrapper for networks
 pub struct Networks<'d> {
     docker: &'d Docker,
 }
 impl<'d> Networks<'d> {
     /// new API interface for networks
-    pub fn new(docker: &'d Docker) -> Networks {
+    pub fn new(docker: &'d Docker) -> Networks<'d> {
         Networks { docker }
     }
     /// List all networks
-    pub async fn list(&self) -> Result<Vec<NetworkData>, Error> {
-        // TODO add universal ListOpts for all listing methods
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn list(&self, opts: &ListNetworksOpts) -> Result<Vec<NetworkData>, Error> {
         let res = self
             .docker
-            .req(Method::GET, "/networks".into(), None, Body::from(""), None)
+            .req(
+                Method::GET,
+                "/networks".into(),
+                Some(opts.to_query()?),
+                Body::from(""),
+                None,
+            )
             .await?;
         let status = res.status().as_u16();
         let text = to_bytes(res.into_body()).await?;
@@ -575,16 +1112,27 @@ impl<'d> Networks<'d> {
 
         match status {
             200 => Ok(serde_json::from_slice(&text)?),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    ///Remove a network
+    /// List networks and get a handle for each of them
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn handles(&self, opts: &ListNetworksOpts) -> Result<Vec<Network<'d>>, Error> {
+        Ok(self
+            .list(opts)
+            .await?
+            .iter()
+            .map(|data| Network::new(self.docker, &data.id))
+            .collect())
+    }
+    /// Remove a network
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn remove(&self, id: &str) -> Result<(), Error> {
         let res = self
             .docker
             .req(
-                Method::GET,
+                Method::DELETE,
                 format!("/networks/{}", id),
                 None,
                 Body::from(""),
@@ -595,12 +1143,63 @@ impl<'d> Networks<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
 
+        match status {
+            204 => Ok(()),
+            403 => err_msg!(
+                text,
+                403,
+                "operation not supported for pre-defined networks"
+            ),
+            404 => err_msg!(text, 404, "no such network"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Inspect a network  
+    /// Returns details of the network including containers connected to it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn inspect(&self, id: &str) -> Result<NetworkInspect, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::GET,
+                format!("/networks/{}", id),
+                None,
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
         match status {
             200 => Ok(serde_json::from_slice(&text)?),
-            403 => err_msg!(text, "operation not supported for pre-defined networks"),
-            404 => err_msg!(text, "no such network"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such network"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Delete unused networks  
+    /// Filters like until or label can be used to limit what gets deleted.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn prune(&self, filters: &FilterBuilder) -> Result<NetworksPruned, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                "/networks/prune".into(),
+                Some(filters.to_query()?),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
 }
@@ -608,6 +1207,66 @@ impl<'d> Networks<'d> {
 
 // * Images start *
 
+/// Api wrapper for a single image
+/// ```ignore
+/// let image = d.image("user/image");
+/// let mut progress = image.push(Some("v1"), &auth).await?;
+/// while let Some(msg) = progress.next().await {
+///     println!("{:?}", msg?);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Image<'d> {
+    docker: &'d Docker,
+    pub name: String,
+}
+impl<'d> Image<'d> {
+    /// new API interface for an image
+    pub fn new<S: Into<String>>(docker: &'d Docker, name: S) -> Image<'d> {
+        Image {
+            docker,
+            name: name.into(),
+        }
+    }
+    /// Pushes the image to a registry  
+    /// Fails if tag differs from the one in the image name.  
+    /// Returns a stream of push progress messages.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, auth)))]
+    pub async fn push(
+        &self,
+        tag: Option<&str>,
+        auth: &AuthOpts,
+    ) -> Result<impl Stream<Item = Result<PushProgress, Error>>, Error> {
+        let mut opts = PushImageOpts::new();
+        if let Some(tag) = tag {
+            opts.tag(tag);
+        }
+        Images::new(self.docker).push(&self.name, &opts, auth).await
+    }
+    /// Inspect the image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn inspect(&self) -> Result<ImageInspect, Error> {
+        Images::new(self.docker).inspect(&self.name).await
+    }
+    /// History of the image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn history(&self) -> Result<Vec<ImageHistory>, Error> {
+        Images::new(self.docker).history(&self.name).await
+    }
+    /// Tag the image so that it becomes part of a repository
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn tag(&self, repo: &str, tag: &str) -> Result<(), Error> {
+        Images::new(self.docker).tag(&self.name, repo, tag).await
+    }
+    /// Remove the image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn remove(&self, force: bool, no_prune: bool) -> Result<(), Error> {
+        Images::new(self.docker)
+            .remove(&self.name, force, no_prune)
+            .await
+    }
+}
+
 /// Api wrapper for images
 pub struct Images<'d> {
     docker: &'d Docker,
@@ -618,13 +1277,14 @@ impl<'d> Images<'d> {
         Images { docker }
     }
     /// List all images
-    pub async fn list(&self) -> Result<Vec<ImageData>, Error> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn list(&self, opts: &ListImagesOpts) -> Result<Vec<ImageData>, Error> {
         let res = self
             .docker
             .req(
                 Method::GET,
                 "/images/json".into(),
-                None,
+                Some(opts.to_query()?),
                 Body::from(""),
                 None,
             )
@@ -635,20 +1295,78 @@ impl<'d> Images<'d> {
 
         match status {
             200 => Ok(serde_json::from_slice(&text)?),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
+    /// List images and get a handle for each of them
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn handles(&self, opts: &ListImagesOpts) -> Result<Vec<Image<'d>>, Error> {
+        Ok(self
+            .list(opts)
+            .await?
+            .iter()
+            .map(|data| Image::new(self.docker, data.id.to_string()))
+            .collect())
+    }
     /// Pulls an image from registry  
+    /// Returns a stream of pull progress messages.  
     /// WARNING!  
     /// not specyfying tag will pull all tags of image
-    pub async fn pull(&self, image: &str, tag: &str, auth: &AuthOpts) -> Result<(), Error> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, auth)))]
+    pub async fn pull(
+        &self,
+        image: &str,
+        tag: &str,
+        auth: &AuthOpts,
+    ) -> Result<impl Stream<Item = Result<PullProgress, Error>>, Error> {
+        ImageRef::parse(image)?;
         let mut opts = CreateImageOpts::new();
-        opts.from_image(image).tag(tag).set_auth(&auth);
+        opts.from_image(image).tag(tag).set_auth(auth);
         self.create(&opts).await
     }
-    /// Create an image by either pulling it from a registry or importing it.
-    pub async fn create(&self, opts: &CreateImageOpts) -> Result<(), Error> {
+    /// Pushes an image to a registry  
+    /// The tag can be part of the name or set in opts, an error is returned if they differ.  
+    /// Returns a stream of push progress messages, the last one carries the digest of the image.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, auth)))]
+    pub async fn push(
+        &self,
+        name: &str,
+        opts: &PushImageOpts,
+        auth: &AuthOpts,
+    ) -> Result<impl Stream<Item = Result<PushProgress, Error>>, Error> {
+        let (path, query) = push_request(name, opts)?;
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                path,
+                Some(query),
+                Body::from(""),
+                Some(vec![("X-Registry-Auth", auth.serialize()?)]),
+            )
+            .await?;
+        let status = res.status().as_u16();
+        match status {
+            200 => Ok(progress_stream(res.into_body())),
+            other => {
+                let text = to_bytes(res.into_body()).await?;
+                trace!("{}", str::from_utf8(&text)?);
+                match other {
+                    404 => err_msg!(text, 404, "no such image"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
+                }
+            }
+        }
+    }
+    /// Create an image by either pulling it from a registry or importing it.  
+    /// Returns a stream of progress messages, the image is created once the stream ends.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn create(
+        &self,
+        opts: &CreateImageOpts,
+    ) -> Result<impl Stream<Item = Result<PullProgress, Error>>, Error> {
         let mut headers = Vec::new();
         if opts.opts().get("fromImage").is_some() {
             headers.push(("X-Registry-Auth", opts.auth_ref().serialize()?));
@@ -658,23 +1376,27 @@ impl<'d> Images<'d> {
             .req(
                 Method::POST,
                 "/images/create".into(),
-                None,
+                Some(opts.to_query()?),
                 Body::from(""),
                 Some(headers),
             )
             .await?;
         let status = res.status().as_u16();
-        let text = to_bytes(res.into_body()).await?;
-        trace!("{}", str::from_utf8(&text)?);
-
         match status {
-            200 => Ok(()),
-            404 => err_msg!(text, "repository does not exist or no read access"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            200 => Ok(progress_stream(res.into_body())),
+            other => {
+                let text = to_bytes(res.into_body()).await?;
+                trace!("{}", str::from_utf8(&text)?);
+                match other {
+                    404 => err_msg!(text, 404, "no such repository"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
+                }
+            }
         }
     }
     /// Remove an image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn remove(&self, image: &str, force: bool, no_prune: bool) -> Result<(), Error> {
         let res = self
             .docker
@@ -692,38 +1414,61 @@ impl<'d> Images<'d> {
 
         match status {
             200 => Ok(()),
-            404 => err_msg!(text, "no such image"),
-            409 => err_msg!(text, "conflict"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such image"),
+            409 => err_msg!(text, 409, "conflict"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Import images  
     /// Load a set of images and tags into a repository.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, archive)))]
     pub async fn import(&self, archive: &[u8]) -> Result<(), Error> {
+        self.load(Body::from(archive.to_vec())).await
+    }
+    /// Import images from a stream of archive chunks  
+    /// Unlike `import()` the archive doesn't have to be kept in memory.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, stream)))]
+    pub async fn load_stream(
+        &self,
+        stream: impl Stream<Item = Result<Bytes, io::Error>> + Send + Sync + 'static,
+    ) -> Result<(), Error> {
+        self.load(Body::wrap_stream(stream)).await
+    }
+    // Loads images from the archive in body
+    async fn load(&self, archive: Body) -> Result<(), Error> {
         let res = self
             .docker
-            .req(
-                Method::POST,
-                "/images/load".into(),
-                None,
-                Body::from(archive.to_vec()),
-                None,
-            )
+            .req(Method::POST, "/images/load".into(), None, archive, None)
             .await?;
         let status = res.status().as_u16();
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
             200 => Ok(()),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
+    /// Import an image from an archive at the url  
+    /// The daemon downloads the archive itself, returns once the import is finished.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn import_url(&self, url: &str) -> Result<(), Error> {
+        let mut opts = CreateImageOpts::new();
+        opts.from_src(url);
+        self.create(&opts)
+            .await?
+            .try_for_each(|progress| async move {
+                trace!("{:?}", progress);
+                Ok(())
+            })
+            .await
+    }
     /// Tag an image so that it becomes part of a repository.  
     /// **image** - name or id of image in the form: *someimage:sometag*  
     /// **repo** - The repository to tag in. For example, *someuser/someimage*  
     /// **tag** - The name of the new tag.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn tag(&self, image: &str, repo: &str, tag: &str) -> Result<(), Error> {
         let res = self
             .docker
@@ -739,16 +1484,42 @@ impl<'d> Images<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            201 => Ok(()),
-            400 => err_msg!(text, "bad parameter"),
-            404 => err_msg!(text, "no such image"),
-            409 => err_msg!(text, "conflict"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            // docker returns 201 but some compatible daemons respond with 200
+            200 | 201 => Ok(()),
+            400 => err_msg!(text, 400, "bad parameter"),
+            404 => err_msg!(text, 404, "no such image"),
+            409 => err_msg!(text, 409, "conflict"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Create a new image from a container  
+    /// Returns the id of created image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn commit(&self, opts: &ImageCommitOpts) -> Result<String, Error> {
+        let res = self
+            .docker
+            .req(
+                Method::POST,
+                "/commit".into(),
+                Some(opts.to_query()?),
+                Body::from(serde_json::to_string(opts._config())?),
+                Some(vec![("Content-type", "application/json".into())]),
+            )
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(&text)?);
+        match status {
+            201 => Ok(serde_json::from_slice::<ImageCommitOut>(&text)?.id),
+            404 => err_msg!(text, 404, "no such container"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Inspect an image  
     /// Return low-level information about an image.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn inspect(&self, image: &str) -> Result<ImageInspect, Error> {
         let res = self
             .docker
@@ -765,13 +1536,14 @@ impl<'d> Images<'d> {
         trace!("{}", str::from_utf8(&text)?);
         match status {
             200 => Ok(serde_json::from_slice(&text)?),
-            404 => err_msg!(text, "no such image"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such image"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
     /// Get the history of an image  
     /// Return parent layers of an image
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn history(&self, image: &str) -> Result<Vec<ImageHistory>, Error> {
         let res = self
             .docker
@@ -788,24 +1560,21 @@ impl<'d> Images<'d> {
         trace!("{}", str::from_utf8(&text)?);
         match status {
             200 => Ok(serde_json::from_slice(&text)?),
-            404 => err_msg!(text, "no such image"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            404 => err_msg!(text, 404, "no such image"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Search for images on Docker Hub
-    pub async fn search(
-        &self,
-        term: &str,
-        limit: u64,
-        filters: String,
-    ) -> Result<Vec<ImageMatch>, Error> {
+    /// Search for images on Docker Hub  
+    /// The number of results is limited with `SearchOpts::limit()`
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn search(&self, opts: &SearchOpts) -> Result<Paginated<ImageMatch>, Error> {
         let res = self
             .docker
             .req(
                 Method::GET,
                 "/images/search".into(),
-                Some(format!("term={}&limit={}&filters={}", term, limit, filters)),
+                Some(opts.to_query()?),
                 Body::from(""),
                 None,
             )
@@ -814,20 +1583,22 @@ impl<'d> Images<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            200 => Ok(serde_json::from_slice(&text)?),
-            404 => err_msg!(text, "no such image"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            200 => Ok(serde_json::from_slice::<Vec<ImageMatch>>(&text)?.into()),
+            404 => err_msg!(text, 404, "no such image"),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Delete unused images
-    pub async fn prune(&self, filters: &str) -> Result<ImagesDeleted, Error> {
+    /// Delete unused images  
+    /// Filters like dangling, until or label can be used to limit what gets deleted.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn prune(&self, filters: &FilterBuilder) -> Result<ImagesPruned, Error> {
         let res = self
             .docker
             .req(
                 Method::POST,
                 "/images/prune".into(),
-                Some(format!("filters={}", filters)),
+                Some(filters.to_query()?),
                 Body::from(""),
                 None,
             )
@@ -836,47 +1607,738 @@ impl<'d> Images<'d> {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(&text)?);
         match status {
-            200 => Ok(serde_json::from_slice(&text).unwrap_or_default()),
-            404 => err_msg!(text, "no such image"),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, ""),
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
         }
     }
-    /// Build an image from a tar archive with a Dockerfile in it.
-    ///The Dockerfile specifies how the image is built from the tar archive. It is typically in the archive's root, but can be at a different path or have a different name by specifying the dockerfile parameter. See the Dockerfile reference for more information.
-    //The Docker daemon performs a preliminary validation of the Dockerfile before starting the build, and returns an error if the syntax is incorrect. After that, each instruction is run one-by-one until the ID of the new image is output.
-    pub async fn build(&self, archive: &[u8], opts: &ImageBuilderOpts) -> Result<String, Error> {
+    /// Build an image from a tar archive with a Dockerfile in it.  
+    /// The Dockerfile specifies how the image is built from the tar archive. It is typically in the archive's root, but can be at a different path or have a different name by specifying the dockerfile parameter.  
+    /// Returns a stream of build output, each instruction is run one-by-one until the ID of the new image is output.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, context_tar)))]
+    pub async fn build_local(
+        &self,
+        context_tar: Vec<u8>,
+        opts: &ImageBuilderOpts,
+    ) -> Result<impl Stream<Item = Result<BuildOutput, Error>>, Error> {
+        self.build(
+            Body::from(context_tar),
+            opts,
+            Some(vec![("Content-type", "application/x-tar".into())]),
+        )
+        .await
+    }
+    /// Build an image from a tar archive sending the build output on the channel.  
+    /// Returns once the build finished, fails with the error message of the daemon if the build failed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, context_tar, tx)))]
+    pub async fn build_with_progress(
+        &self,
+        context_tar: Bytes,
+        opts: &ImageBuilderOpts,
+        mut tx: Sender<BuildOutput>,
+    ) -> Result<(), Error> {
+        let mut output = Box::pin(
+            self.build(
+                Body::from(context_tar),
+                opts,
+                Some(vec![("Content-type", "application/x-tar".into())]),
+            )
+            .await?,
+        );
+        while let Some(msg) = output.next().await {
+            let msg = msg?;
+            if let Some(e) = &msg.error {
+                return Err(format_err!("{}", e));
+            }
+            // The build goes on even if nobody listens to the progress anymore
+            if tx.send(msg).await.is_err() {
+                trace!("build progress receiver dropped");
+            }
+        }
+        Ok(())
+    }
+    /// Build an image from a tar archive with credentials for private registries of base images.  
+    /// **registry_configs** - map of registry addresses to the credentials used to pull from them.  
+    /// Returns a stream of build output.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(self, context_tar, registry_configs))
+    )]
+    pub async fn build_authenticated(
+        &self,
+        context_tar: Bytes,
+        opts: &ImageBuilderOpts,
+        registry_configs: &HashMap<&str, &AuthOpts>,
+    ) -> Result<impl Stream<Item = Result<BuildOutput, Error>>, Error> {
+        self.build(
+            Body::from(context_tar),
+            opts,
+            Some(vec![
+                ("Content-type", "application/x-tar".into()),
+                ("X-Registry-Config", registry_config(registry_configs)?),
+            ]),
+        )
+        .await
+    }
+    /// Build an image from a remote Git repository or context url set with `ImageBuilderOpts::remote()`.  
+    /// Returns a stream of build output.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn build_remote(
+        &self,
+        opts: &ImageBuilderOpts,
+    ) -> Result<impl Stream<Item = Result<BuildOutput, Error>>, Error> {
+        self.build(Body::from(""), opts, None).await
+    }
+    async fn build(
+        &self,
+        body: Body,
+        opts: &ImageBuilderOpts,
+        headers: Option<Vec<(&'static str, String)>>,
+    ) -> Result<impl Stream<Item = Result<BuildOutput, Error>>, Error> {
+        match opts._output_type() {
+            None | Some("image") | Some("moby") => {}
+            Some(other) => {
+                return Err(format_err!(
+                    "unsupported build output type {}, only image and moby can be exported without a BuildKit session",
+                    other
+                ))
+            }
+        }
+        let headers = match headers {
+            None if opts._buildkit() => Some(vec![("Content-type", "application/x-tar".into())]),
+            headers => headers,
+        };
         let res = self
             .docker
             .req(
                 Method::POST,
                 "/build".into(),
                 Some(opts.to_query()?),
-                Body::from(archive.to_vec()),
-                Some(vec![("Content-type", "application/x-tar".into())]),
+                body,
+                headers,
             )
             .await?;
         let status = res.status().as_u16();
         match status {
-            200 => {
-                let mut out = Vec::new();
-                let mut stream = res.into_body();
-                while let Some(chunk) = stream.next().await {
-                    let bytes = chunk?;
-                    out = [&out[..], &bytes[..]].concat();
-                }
-                Ok(str::from_utf8(&out[..])?.to_string())
-            }
+            200 => Ok(json_stream(res.into_body())),
             other => {
                 let text = to_bytes(res.into_body()).await?;
                 trace!("{}", str::from_utf8(&text)?);
                 match other {
-                    404 => err_msg!(text, "no such image"),
-                    500 => err_msg!(text, "server error"),
-                    _ => err_msg!(text, ""),
+                    400 => err_msg!(text, 400, "bad parameter"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
                 }
             }
         }
     }
 }
+// Path and query of a push request. The tag is only sent in the query, taken
+// from opts or else from the name, so the two can't silently disagree.
+fn push_request(name: &str, opts: &PushImageOpts) -> Result<(String, String), Error> {
+    let image = ImageRef::parse(name)?;
+    if image.digest.is_some() {
+        return Err(format_err!("cannot push an image by digest: {}", name));
+    }
+    let opts_tag = opts.opts().get("tag").and_then(Value::as_str);
+    let tag = match (opts_tag, image.tag.as_deref()) {
+        (Some(a), Some(b)) if a != b => {
+            return Err(format_err!("tag {} conflicts with image {}", a, name));
+        }
+        (Some(tag), _) | (None, Some(tag)) => Some(tag),
+        (None, None) => None,
+    };
+    let mut query = PushImageOpts::new();
+    if let Some(tag) = tag {
+        query.tag(tag);
+    }
+    let repo = ImageRef { tag: None, ..image };
+    Ok((format!("/images/{}/push", repo), query.to_query()?))
+}
+
 // * Images End *
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server};
+    use std::convert::Infallible;
+
+    // Starts a server answering every request with status and body, returns its url
+    fn mock_docker<B: Into<Bytes>>(status: u16, body: B) -> String {
+        let body = body.into();
+        let make_svc = make_service_fn(move |_| {
+            let body = body.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_| {
+                    let body = body.clone();
+                    async move {
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from(body))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    // Directory unique to a test, removed with everything in it when dropped
+    struct TestDir(std::path::PathBuf);
+    impl TestDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("wharf-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+    }
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn body(chunks: &[&'static str]) -> Body {
+        Body::wrap_stream(stream::iter(
+            chunks
+                .iter()
+                .map(|c| Ok::<_, std::io::Error>(*c))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    #[test]
+    fn json_stream_joins_split_objects() {
+        let chunks = body(&[
+            "{\"stream\":\"Step 1/2 : FROM alpine\\n\"}\r\n{\"str",
+            "eam\":\"Step 2/2\"}\r\n",
+            "{\"aux\":{\"ID\":\"sha256:1234\"}}",
+        ]);
+        let out: Vec<BuildOutput> = block_on(json_stream(chunks).collect::<Vec<_>>())
+            .into_iter()
+            .map(|o| o.unwrap())
+            .collect();
+
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].stream.as_deref(), Some("Step 1/2 : FROM alpine\n"));
+        assert_eq!(out[1].stream.as_deref(), Some("Step 2/2"));
+        assert!(out[2].aux.is_some());
+    }
+
+    #[test]
+    fn progress_stream_returns_errors() {
+        let chunks = body(&[
+            "{\"status\":\"Pulling from library/alpine\",\"id\":\"latest\"}\r\n",
+            "{\"status\":\"Downloading\",\"progressDetail\":{\"current\":1,\"total\":2},\"id\":\"c9b1b535fdd9\"}\r\n",
+            "{\"error\":\"manifest unknown\",\"errorDetail\":{\"message\":\"manifest unknown\"}}\r\n",
+        ]);
+        let out: Vec<Result<PullProgress, Error>> =
+            block_on(progress_stream(chunks).collect::<Vec<_>>());
+
+        assert_eq!(out.len(), 3);
+        let progress = out[1].as_ref().unwrap();
+        assert_eq!(progress.status, "Downloading");
+        let detail = progress.progress_detail.as_ref().unwrap();
+        assert_eq!((detail.current, detail.total), (Some(1), Some(2)));
+        assert_eq!(out[2].as_ref().unwrap_err().to_string(), "manifest unknown");
+    }
+    #[test]
+    fn demux_stream_splits_frames() {
+        let chunks = body(&[
+            "\u{1}\u{0}\u{0}\u{0}\u{0}\u{0}\u{0}\u{3}out\u{2}\u{0}\u{0}",
+            "\u{0}\u{0}\u{0}\u{0}\u{4}er",
+            "r\n",
+        ]);
+        let out: Vec<LogFrame> = block_on(demux_stream(chunks).collect::<Vec<_>>())
+            .into_iter()
+            .map(|f| f.unwrap())
+            .collect();
+
+        assert_eq!(
+            out,
+            vec![
+                LogFrame::Stdout(b"out".to_vec()),
+                LogFrame::Stderr(b"err\n".to_vec())
+            ]
+        );
+    }
+    #[test]
+    fn json_stream_reports_invalid_json() {
+        let chunks = body(&["{\"stream\":\"ok\"}", "not json"]);
+        let out: Vec<Result<BuildOutput, Error>> =
+            block_on(json_stream(chunks).collect::<Vec<_>>());
+
+        assert_eq!(out.len(), 2);
+        assert!(out[0].is_ok());
+        assert!(out[1].is_err());
+    }
+    #[tokio::test]
+    async fn rename_updates_id() {
+        let d = Docker::new(&mock_docker(204, "")).unwrap();
+        let mut container = d.container("old_name");
+        container.rename("new_name").await.unwrap();
+
+        assert_eq!(container.id, "new_name");
+    }
+    #[tokio::test]
+    async fn failed_rename_keeps_id() {
+        let d = Docker::new(&mock_docker(409, "{\"message\":\"name in use\"}")).unwrap();
+        let mut container = d.container("old_name");
+
+        assert!(container.rename("new_name").await.is_err());
+        assert_eq!(container.id, "old_name");
+    }
+    #[tokio::test]
+    async fn start_reports_already_running() {
+        let d = Docker::new(&mock_docker(204, "")).unwrap();
+        assert_eq!(
+            d.container("test").start().await.unwrap(),
+            StartResult::Started
+        );
+
+        let d = Docker::new(&mock_docker(304, "")).unwrap();
+        assert_eq!(
+            d.container("test").start().await.unwrap(),
+            StartResult::AlreadyRunning
+        );
+    }
+    #[tokio::test]
+    async fn stop_reports_already_stopped() {
+        let d = Docker::new(&mock_docker(204, "")).unwrap();
+        assert_eq!(
+            d.container("test").stop().await.unwrap(),
+            StopResult::Stopped
+        );
+
+        let d = Docker::new(&mock_docker(304, "")).unwrap();
+        assert_eq!(
+            d.container("test").stop().await.unwrap(),
+            StopResult::AlreadyStopped
+        );
+    }
+    #[tokio::test]
+    async fn tag_succeeds_on_created() {
+        let d = Docker::new(&mock_docker(201, "")).unwrap();
+
+        assert!(d.images().tag("alpine", "user/alpine", "v1").await.is_ok());
+    }
+    #[tokio::test]
+    async fn tag_reports_bad_parameter() {
+        let d = Docker::new(&mock_docker(400, "{\"message\":\"invalid tag format\"}")).unwrap();
+        let err = d
+            .images()
+            .tag("alpine", "user/alpine", "v1:")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "bad parameter - invalid tag format");
+    }
+    #[tokio::test]
+    async fn errors_downcast_to_docker_api_error() {
+        let d = Docker::new(&mock_docker(
+            404,
+            "{\"message\":\"No such container: test\"}",
+        ))
+        .unwrap();
+        let err = d.container("test").start().await.unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<DockerApiError>(),
+            Some(&DockerApiError::NotFound {
+                resource: "container".into(),
+                message: "No such container: test".into()
+            })
+        );
+    }
+    #[tokio::test]
+    async fn errors_include_cause() {
+        let d = Docker::new(&mock_docker(
+            500,
+            "{\"message\":\"cannot start container\",\"cause\":\"mount denied\"}",
+        ))
+        .unwrap();
+        let err = d.container("test").start().await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "server error - cannot start container: mount denied"
+        );
+    }
+    #[test]
+    fn push_request_moves_tag_to_query() {
+        let mut v1 = PushImageOpts::new();
+        v1.tag("v1");
+        let none = PushImageOpts::new();
+
+        assert_eq!(
+            push_request("localhost:5000/user/image:v1", &none).unwrap(),
+            (
+                "/images/localhost:5000/user/image/push".to_string(),
+                "tag=v1".to_string()
+            )
+        );
+        assert_eq!(
+            push_request("user/image", &v1).unwrap(),
+            ("/images/user/image/push".to_string(), "tag=v1".to_string())
+        );
+        assert_eq!(
+            push_request("user/image:v1", &v1).unwrap().1,
+            "tag=v1".to_string()
+        );
+        assert_eq!(push_request("user/image", &none).unwrap().1, "");
+        assert_eq!(
+            push_request("user/image:v2", &v1).unwrap_err().to_string(),
+            "tag v1 conflicts with image user/image:v2"
+        );
+        assert!(push_request("user/image@sha256:abc", &none).is_err());
+    }
+    #[tokio::test]
+    async fn push_streams_progress() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"status\":\"Pushed\",\"id\":\"c9b1b535fdd9\"}\r\n{\"status\":\"\",\"aux\":{\"Tag\":\"v1\",\"Digest\":\"sha256:abc\",\"Size\":528}}\r\n",
+        ))
+        .unwrap();
+        let mut opts = PushImageOpts::new();
+        opts.tag("v1");
+        let out: Vec<_> = d
+            .images()
+            .push("user/image", &opts, &AuthOpts::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 2);
+        let out: Vec<_> = d
+            .image("user/image")
+            .push(Some("v1"), &AuthOpts::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].as_ref().unwrap().status, "Pushed");
+        assert_eq!(
+            out[1].as_ref().unwrap().aux.as_ref().unwrap().digest,
+            "sha256:abc"
+        );
+    }
+    #[tokio::test]
+    async fn for_each_concurrent_runs_on_every_container() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "[{\"Id\":\"a\",\"Names\":[],\"Image\":\"alpine\",\"ImageID\":\"\",\"Command\":\"\",\"Created\":0,\"State\":\"running\",\"Status\":\"\",\"Ports\":[],\"HostConfig\":{},\"NetworkSettings\":{},\"Mounts\":[]},\
+              {\"Id\":\"b\",\"Names\":[],\"Image\":\"alpine\",\"ImageID\":\"\",\"Command\":\"\",\"Created\":0,\"State\":\"running\",\"Status\":\"\",\"Ports\":[],\"HostConfig\":{},\"NetworkSettings\":{},\"Mounts\":[]}]",
+        ))
+        .unwrap();
+        let results = d
+            .containers()
+            .for_each_concurrent(&ListContainersOpts::new(), |c| async move {
+                match &*c.id {
+                    "a" => Ok(()),
+                    id => Err(format_err!("failed on {}", id)),
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+    }
+    #[tokio::test]
+    async fn import_url_reports_progress_errors() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"status\":\"Downloading\"}\r\n{\"error\":\"archive not found\"}\r\n",
+        ))
+        .unwrap();
+        let err = d
+            .images()
+            .import_url("http://example.com/image.tar")
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "archive not found");
+    }
+    #[tokio::test]
+    async fn load_stream_sends_chunks() {
+        let d = Docker::new(&mock_docker(200, "")).unwrap();
+        let chunks = stream::iter(vec![
+            Ok(Bytes::from_static(b"archive-")),
+            Ok(Bytes::from_static(b"bytes")),
+        ]);
+
+        assert!(d.images().load_stream(chunks).await.is_ok());
+    }
+    #[tokio::test]
+    async fn create_returns_handle() {
+        let d = Docker::new(&mock_docker(
+            201,
+            "{\"Id\":\"e90e34656806\",\"Warnings\":[]}",
+        ))
+        .unwrap();
+        let container = d
+            .containers()
+            .create("test", &ContainerBuilderOpts::new())
+            .await
+            .unwrap();
+
+        assert_eq!(container.id, "e90e34656806");
+    }
+    #[tokio::test]
+    async fn networks_handles_use_ids() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "[{\"Name\":\"bridge\",\"Id\":\"f2de39df4171\",\"Created\":\"2016-10-19T06:21:00.416543526Z\",\"Scope\":\"local\",\"Driver\":\"bridge\",\"EnableIPv6\":false,\"Internal\":false,\"Attachable\":false,\"Ingress\":false,\"IPAM\":{},\"Options\":{},\"Containers\":null}]",
+        ))
+        .unwrap();
+        let networks = d
+            .networks()
+            .handles(&ListNetworksOpts::new())
+            .await
+            .unwrap();
+
+        assert_eq!(networks.len(), 1);
+        assert_eq!(networks[0].id, "f2de39df4171");
+    }
+    // Daemon running an exec instance that writes output to the start response
+    fn mock_exec_daemon(output: &'static [u8]) -> String {
+        let (url, _) = crate::tests::mock_daemon(move |request, _| match request {
+            "POST /containers/abc/exec" => (201, "{\"Id\":\"e1\"}".into()),
+            "POST /exec/e1/start" => (200, output.to_vec()),
+            "GET /exec/e1/json" => (
+                200,
+                crate::result::tests::exec_inspect()
+                    .to_string()
+                    .into_bytes(),
+            ),
+            _ => (404, Vec::new()),
+        });
+        url
+    }
+    #[tokio::test]
+    async fn exec_split_separates_streams() {
+        let d = Docker::new(&mock_exec_daemon(
+            b"\x01\x00\x00\x00\x00\x00\x00\x04out1\x02\x00\x00\x00\x00\x00\x00\x03err\x01\x00\x00\x00\x00\x00\x00\x04out2",
+        ))
+        .unwrap();
+        let mut opts = ExecOpts::new();
+        opts.cmd(&["sh", "-c", "exit 2"]);
+        let out = d.container("abc").exec_split(&opts).await.unwrap();
+
+        assert_eq!(out.stdout, "out1out2");
+        assert_eq!(out.stderr, "err");
+        assert_eq!(out.exit_code, 2);
+    }
+    #[tokio::test]
+    async fn exec_split_with_tty_returns_stdout() {
+        let d = Docker::new(&mock_exec_daemon(b"out and err")).unwrap();
+        let mut opts = ExecOpts::new();
+        opts.cmd(&["sh", "-c", "exit 2"]).tty(true);
+        let out = d.container("abc").exec_split(&opts).await.unwrap();
+
+        assert_eq!(out.stdout, "out and err");
+        assert_eq!(out.stderr, "");
+        assert_eq!(out.exit_code, 2);
+    }
+    #[tokio::test]
+    async fn networks_remove_sends_delete() {
+        let (url, requests) = crate::tests::mock_daemon(|request, _| match request {
+            "DELETE /networks/n1" => (204, Vec::new()),
+            _ => (404, "{\"message\":\"no such network\"}".into()),
+        });
+        let d = Docker::new(&url).unwrap();
+        d.networks().remove("n1").await.unwrap();
+        d.network("n1").remove().await.unwrap();
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["DELETE /networks/n1", "DELETE /networks/n1"]
+        );
+    }
+    #[tokio::test]
+    async fn images_handles_use_ids() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "[{\"Id\":\"sha256:e216a057b1cb\",\"ParentId\":\"\",\"RepoTags\":[\"alpine:latest\"],\"RepoDigests\":[],\"Created\":1474925151,\"Size\":103579269,\"VirtualSize\":103579269,\"SharedSize\":0,\"Labels\":null,\"Containers\":2}]",
+        ))
+        .unwrap();
+        let images = d.images().handles(&ListImagesOpts::new()).await.unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].name, "sha256:e216a057b1cb");
+    }
+    #[tokio::test]
+    async fn push_rejects_invalid_reference() {
+        let d = Docker::new(&mock_docker(200, "")).unwrap();
+        let err = d
+            .images()
+            .push("User/Image", &PushImageOpts::new(), &AuthOpts::new())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.to_string(), "invalid image reference: User/Image");
+    }
+    #[tokio::test]
+    async fn build_with_progress_sends_output() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"stream\":\"Step 1/1 : FROM alpine\"}\r\n{\"aux\":{\"ID\":\"sha256:1234\"}}\r\n",
+        ))
+        .unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        d.images()
+            .build_with_progress(Bytes::new(), &ImageBuilderOpts::new(), tx)
+            .await
+            .unwrap();
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.stream.as_deref(), Some("Step 1/1 : FROM alpine"));
+        assert!(rx.recv().await.unwrap().aux.is_some());
+        assert!(rx.recv().await.is_none());
+    }
+    #[tokio::test]
+    async fn build_rejects_outputs_needing_a_session() {
+        let d = Docker::new(&mock_docker(200, "")).unwrap();
+        let mut opts = ImageBuilderOpts::new();
+        opts.output("type=tar,dest=out.tar");
+        let err = d
+            .images()
+            .build_local(Vec::new(), &opts)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "unsupported build output type tar, only image and moby can be exported without a BuildKit session"
+        );
+
+        opts.output("type=image,name=app:v1");
+        assert!(d.images().build_local(Vec::new(), &opts).await.is_ok());
+    }
+    #[tokio::test]
+    async fn build_with_progress_fails_on_error() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"stream\":\"Step 1/1 : RUN false\"}\r\n{\"error\":\"returned a non-zero code: 1\"}\r\n",
+        ))
+        .unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let err = d
+            .images()
+            .build_with_progress(Bytes::new(), &ImageBuilderOpts::new(), tx)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "returned a non-zero code: 1");
+    }
+    #[tokio::test]
+    async fn copy_from_extracts_archive() {
+        let mut archive = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive
+            .append_data(&mut header, "hosts", &b"hello"[..])
+            .unwrap();
+        let d = Docker::new(&mock_docker(200, archive.into_inner().unwrap())).unwrap();
+
+        let dir = TestDir::new("copy_from_extracts_archive");
+        let dest = dir.0.join("dest");
+        d.container("test")
+            .copy_from("/etc/hosts", &dest)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(dest.join("hosts")).unwrap(), b"hello");
+    }
+    #[tokio::test]
+    async fn copy_to_archives_local_path() {
+        let d = Docker::new(&mock_docker(200, "")).unwrap();
+        let dir = TestDir::new("copy_to_archives_local_path");
+        let src = dir.0.join("hosts");
+        std::fs::write(&src, b"hello").unwrap();
+
+        let container = d.container("test");
+        assert!(container.copy_to(&src, "/tmp").await.is_ok());
+        std::fs::remove_file(&src).unwrap();
+        assert!(container.copy_to(&src, "/tmp").await.is_err());
+    }
+    #[tokio::test]
+    async fn containers_prune_returns_deleted() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"ContainersDeleted\":[\"8dfafdbc3a40\"],\"SpaceReclaimed\":1024}",
+        ))
+        .unwrap();
+        let mut filters = FilterBuilder::new();
+        filters.filter("until", "24h");
+        let pruned = d.containers().prune(&filters).await.unwrap();
+
+        assert_eq!(
+            pruned.containers_deleted,
+            Some(vec!["8dfafdbc3a40".to_string()])
+        );
+        assert_eq!(pruned.space_reclaimed, 1024);
+    }
+    #[tokio::test]
+    async fn images_prune_returns_deleted() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "{\"ImagesDeleted\":[{\"Untagged\":\"ubuntu:latest\"},{\"Deleted\":\"sha256:3db9c44f4520\"}],\"SpaceReclaimed\":65536}",
+        ))
+        .unwrap();
+        let mut filters = FilterBuilder::new();
+        filters.filter("dangling", "false");
+        let pruned = d.images().prune(&filters).await.unwrap();
+
+        let deleted = pruned.images_deleted.unwrap();
+        assert_eq!(deleted[0].untagged, "ubuntu:latest");
+        assert_eq!(deleted[1].deleted, "sha256:3db9c44f4520");
+        assert_eq!(pruned.space_reclaimed, 65536);
+    }
+    #[tokio::test]
+    async fn search_returns_page() {
+        let d = Docker::new(&mock_docker(
+            200,
+            "[{\"description\":\"\",\"is_official\":true,\"is_automated\":false,\"name\":\"alpine\",\"star_count\":7000}]",
+        ))
+        .unwrap();
+        let mut opts = SearchOpts::new();
+        opts.term("alpine").limit(1);
+        let page = d.images().search(&opts).await.unwrap();
+
+        assert!(!page.has_more());
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].name, "alpine");
+    }
+    #[tokio::test]
+    async fn export_stream_yields_archive() {
+        let d = Docker::new(&mock_docker(200, "archive-bytes")).unwrap();
+        let chunks: Vec<_> = d
+            .container("test")
+            .export_stream()
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let archive: Vec<u8> = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap().to_vec())
+            .collect();
+
+        assert_eq!(archive, b"archive-bytes".to_vec());
+    }
+}
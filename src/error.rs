@@ -0,0 +1,125 @@
+//! Errors returned by the docker daemon.
+//!
+//! Failed api calls return a `DockerApiError` wrapped in `failure::Error`
+//! so the kind of failure can be inspected with a downcast:
+//! ```ignore
+//! match container.start().await {
+//!     Err(e) => match e.downcast_ref::<DockerApiError>() {
+//!         Some(DockerApiError::NotFound { resource, .. }) => println!("no such {}", resource),
+//!         _ => return Err(e),
+//!     },
+//!     Ok(_) => {}
+//! }
+//! ```
+use failure::Fail;
+use std::fmt;
+
+/// Error response of the docker daemon distinguished by the status code
+#[derive(Debug, Clone, PartialEq)]
+pub enum DockerApiError {
+    /// 404 - the resource doesn't exist
+    NotFound { resource: String, message: String },
+    /// 409 - the operation conflicts with the state of a resource
+    Conflict { message: String },
+    /// 401 or 403 - the operation is not allowed
+    Unauthorized { message: String },
+    /// 500 - the daemon failed to handle the request
+    ServerError { message: String },
+    /// 400 - the request had invalid parameters
+    BadParameter { message: String },
+    /// Any other unexpected status code
+    Other { status: u16, message: String },
+}
+
+impl DockerApiError {
+    // Creates an error from the status code of a response, the context of the failed call
+    // and a message sent by the daemon. If the daemon didn't send one the context is used instead.
+    pub(crate) fn new(status: u16, context: &str, message: Option<String>) -> Self {
+        let message = message.unwrap_or_else(|| context.to_string());
+        match status {
+            400 => DockerApiError::BadParameter { message },
+            401 | 403 => DockerApiError::Unauthorized { message },
+            404 => DockerApiError::NotFound {
+                resource: context.trim_start_matches("no such ").to_string(),
+                message,
+            },
+            409 => DockerApiError::Conflict { message },
+            500 => DockerApiError::ServerError { message },
+            status => DockerApiError::Other { status, message },
+        }
+    }
+    /// Message describing the error, as sent by the daemon if available
+    pub fn message(&self) -> &str {
+        match self {
+            DockerApiError::NotFound { message, .. }
+            | DockerApiError::Conflict { message }
+            | DockerApiError::Unauthorized { message }
+            | DockerApiError::ServerError { message }
+            | DockerApiError::BadParameter { message }
+            | DockerApiError::Other { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for DockerApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            DockerApiError::NotFound { resource, .. } => format!("no such {}", resource),
+            DockerApiError::Conflict { .. } => "conflict".to_string(),
+            DockerApiError::Unauthorized { .. } => "unauthorized".to_string(),
+            DockerApiError::ServerError { .. } => "server error".to_string(),
+            DockerApiError::BadParameter { .. } => "bad parameter".to_string(),
+            DockerApiError::Other { status, .. } => format!("unexpected status {}", status),
+        };
+        match self.message() {
+            message if message.is_empty() || message == kind => write!(f, "{}", kind),
+            message => write!(f, "{} - {}", kind, message),
+        }
+    }
+}
+
+impl Fail for DockerApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_status_codes() {
+        assert_eq!(
+            DockerApiError::new(
+                404,
+                "no such container",
+                Some("No such container: abc".into())
+            ),
+            DockerApiError::NotFound {
+                resource: "container".into(),
+                message: "No such container: abc".into()
+            }
+        );
+        assert_eq!(
+            DockerApiError::new(409, "container is paused", None),
+            DockerApiError::Conflict {
+                message: "container is paused".into()
+            }
+        );
+        assert_eq!(
+            DockerApiError::new(418, "", None),
+            DockerApiError::Other {
+                status: 418,
+                message: "".into()
+            }
+        );
+    }
+    #[test]
+    fn displays_kind_and_message() {
+        let err = DockerApiError::new(400, "bad parameter", Some("invalid tag format".into()));
+        assert_eq!(err.to_string(), "bad parameter - invalid tag format");
+
+        let err = DockerApiError::new(500, "server error", None);
+        assert_eq!(err.to_string(), "server error");
+
+        let err = DockerApiError::new(404, "no such image", None);
+        assert_eq!(err.to_string(), "no such image");
+    }
+}
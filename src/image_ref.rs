@@ -0,0 +1,154 @@
+//! Parsing of image references in the form `[registry/]name[:tag][@digest]`
+//! ```ignore
+//! let image = ImageRef::parse("localhost:5000/user/image:v1")?;
+//! assert_eq!(image.registry.as_deref(), Some("localhost:5000"));
+//! assert_eq!(image.name, "user/image");
+//! assert_eq!(image.tag.as_deref(), Some("v1"));
+//! ```
+use failure::Error;
+use std::fmt;
+
+/// Reference to an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageRef {
+    /// Address of the registry f.e. `registry.example.com:5000`, None for Docker Hub
+    pub registry: Option<String>,
+    /// Path of the repository f.e. `library/alpine`
+    pub name: String,
+    pub tag: Option<String>,
+    /// Content digest f.e. `sha256:e216a057...`
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    /// Parses and validates an image reference
+    pub fn parse(s: &str) -> Result<ImageRef, Error> {
+        let (rest, digest) = match s.find('@') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        // A colon after the last slash separates the tag, any other belongs to the registry port
+        let (rest, tag) = match rest.rfind(':') {
+            Some(i) if !rest[i..].contains('/') => (&rest[..i], Some(&rest[i + 1..])),
+            _ => (rest, None),
+        };
+        let (registry, name) = match rest.find('/') {
+            Some(i) if is_registry(&rest[..i]) => (Some(&rest[..i]), &rest[i + 1..]),
+            _ => (None, rest),
+        };
+
+        let valid = name.split('/').all(is_component)
+            && tag.iter().all(|tag| is_tag(tag))
+            && digest.iter().all(|digest| is_digest(digest));
+        if !valid {
+            return Err(format_err!("invalid image reference: {}", s));
+        }
+        Ok(ImageRef {
+            registry: registry.map(str::to_string),
+            name: name.to_string(),
+            tag: tag.map(str::to_string),
+            digest: digest.map(str::to_string),
+        })
+    }
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(registry) = &self.registry {
+            write!(f, "{}/", registry)?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+// The first path component is a registry if it looks like a hostname
+fn is_registry(s: &str) -> bool {
+    s.contains('.') || s.contains(':') || s == "localhost"
+}
+
+// Path components are lowercase alphanumerics separated by `.`, `_` or `-`
+fn is_component(s: &str) -> bool {
+    let alnum = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    s.starts_with(alnum)
+        && s.ends_with(alnum)
+        && s.chars()
+            .all(|c| alnum(c) || c == '.' || c == '_' || c == '-')
+}
+
+fn is_tag(s: &str) -> bool {
+    s.len() <= 128
+        && !s.starts_with(['.', '-'])
+        && !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+fn is_digest(s: &str) -> bool {
+    match s.find(':') {
+        Some(i) => i > 0 && s.len() > i + 1 && s[i + 1..].chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_references() {
+        assert_eq!(
+            ImageRef::parse("alpine").unwrap(),
+            ImageRef {
+                registry: None,
+                name: "alpine".into(),
+                tag: None,
+                digest: None
+            }
+        );
+        assert_eq!(
+            ImageRef::parse("localhost:5000/user/image:v1.0").unwrap(),
+            ImageRef {
+                registry: Some("localhost:5000".into()),
+                name: "user/image".into(),
+                tag: Some("v1.0".into()),
+                digest: None
+            }
+        );
+        let image = ImageRef::parse("registry.example.com/app@sha256:e216a057b1cb").unwrap();
+        assert_eq!(image.registry.as_deref(), Some("registry.example.com"));
+        assert_eq!(image.digest.as_deref(), Some("sha256:e216a057b1cb"));
+    }
+    #[test]
+    fn displays_reference() {
+        for s in &[
+            "alpine",
+            "user/image:latest",
+            "localhost:5000/user/image:v1",
+            "registry.example.com/app:v2@sha256:e216a057b1cb",
+        ] {
+            assert_eq!(ImageRef::parse(s).unwrap().to_string(), *s);
+        }
+    }
+    #[test]
+    fn rejects_invalid_references() {
+        for s in &[
+            "",
+            "User/Image",
+            "image:",
+            "image:-tag",
+            "image@sha256",
+            "image@sha256:xyz",
+            "/image",
+            "image//name",
+        ] {
+            assert!(ImageRef::parse(s).is_err(), "{} should be invalid", s);
+        }
+    }
+}
@@ -16,19 +16,21 @@
 //!     let mut opts = ListContainersOpts::new();
 //!     opts.all(true);
 //!     // iterate over containers
-//!     for container in containers.list(opts).await? {
+//!     for data in containers.list(&opts).await? {
 //!         // manipulate container
+//!         let container = d.container(&data.id);
 //!         container.start().await?;
 //!         container.stop().await?;
 //!     }
 //!     // Create a container
 //!     let mut container_opts = ContainerBuilderOpts::new();
 //!     container_opts
-//! 	.image("ubuntu")
-//! 	.cmd(&["/bin/echo".into(), "hello".into()])
-//! 	.env(&["HTTPS_PROXY=proxy.domain.com:1337"]);
+//!         .image("ubuntu")
+//!         .cmd(&["/bin/echo".into(), "hello".into()])
+//!         .env(&["HTTPS_PROXY=proxy.domain.com:1337"]);
 //!
-//!     containers.create("jimmy-falcon", &container_opts).await?;
+//!     let container = containers.create("jimmy-falcon", &container_opts).await?;
+//!     container.start().await?;
 //!
 //!     Ok(())
 //! }
@@ -37,54 +39,318 @@
 #![allow(non_snake_case)]
 #[macro_use]
 extern crate failure;
+// Lets code generated by wharf-derive refer to `::wharf` from within this crate
+extern crate self as wharf;
 #[macro_use]
 pub mod api;
+pub mod error;
+pub mod image_ref;
 pub mod opts;
 pub mod result;
 use crate::api::*;
+use crate::error::DockerApiError;
+use crate::logging::*;
 use crate::opts::*;
-use crate::result::ExecInspect;
+use crate::result::{
+    AuthResponse, ContainerId, DiskUsage, EventMessage, ExecInspect, ExecOutput, LogFrame,
+    PruneOut, SystemInfo, SystemPruned, VersionInfo,
+};
 use failure::Error;
+use futures::stream::{Stream, StreamExt};
 use http::header::HeaderValue;
 use http::uri::PathAndQuery;
-use hyper::{body::to_bytes, client::HttpConnector, Body, Method, Request, Response, Uri};
-use log::*;
+use hyper::{
+    body::{to_bytes, HttpBody},
+    client::HttpConnector,
+    Body, Method, Request, Response, Uri,
+};
 use serde::{Deserialize, Serialize};
 use std::str;
 use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(feature = "tls")]
+pub use rustls;
+
+// Logging macros used across the crate, backed by tracing if the feature is enabled
+mod logging {
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) use log::{debug, error, trace, warn};
+    #[cfg(feature = "tracing")]
+    pub(crate) use tracing::{debug, error, trace, warn};
+}
+
+// Prune endpoints called by system_prune() and the filters each of them accepts
+const PRUNE_FILTERS: &[(&str, &[&str])] = &[
+    ("/containers/prune", &["until", "label", "label!"]),
+    ("/networks/prune", &["until", "label", "label!"]),
+    ("/images/prune", &["dangling", "until", "label", "label!"]),
+    ("/volumes/prune", &["label", "label!"]),
+    (
+        "/build/prune",
+        &[
+            "until",
+            "id",
+            "parent",
+            "type",
+            "description",
+            "inuse",
+            "shared",
+            "private",
+        ],
+    ),
+];
+
+#[cfg(feature = "tls")]
+type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+#[cfg(not(feature = "tls"))]
+type Connector = HttpConnector;
 
 /// The main interface to interact with an instance of Docker.
 #[derive(Debug)]
 pub struct Docker {
-    client: hyper::Client<HttpConnector>,
+    client: hyper::Client<Connector>,
     url: Uri,
+    api_version: Option<String>,
+    timeout: Option<Duration>,
+    retry: Retry,
+}
+
+// Retry policy for requests that failed to connect to the daemon
+#[derive(Debug, Clone)]
+struct Retry {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+impl Retry {
+    // Backoff doubles with each attempt up to max_backoff
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(1 << attempt.min(31))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Builder for configuring a Docker client
+/// # Example
+/// ```ignore
+/// let d = Docker::builder()
+///     .url("http://0.0.0.0:2376")
+///     .api_version("1.40")
+///     .timeout(Duration::from_secs(30))
+///     .pool_max_idle_per_host(16)
+///     .build()?;
+/// ```
+#[derive(Default)]
+pub struct DockerBuilder {
+    url: Option<String>,
+    api_version: Option<String>,
+    timeout: Option<Duration>,
+    retry: Retry,
+    keep_alive_timeout: Option<Duration>,
+    pool_max_idle: Option<usize>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<rustls::ClientConfig>,
+}
+
+impl DockerBuilder {
+    pub fn new() -> Self {
+        DockerBuilder::default()
+    }
+    /// Url of the docker daemon f.e. http://0.0.0.0:2376
+    pub fn url(&mut self, url: &str) -> &mut Self {
+        self.url = Some(url.to_string());
+        self
+    }
+    /// Version of docker api to use f.e. 1.40  
+    /// By default requests are unversioned and the daemon uses its latest api version.
+    pub fn api_version(&mut self, version: &str) -> &mut Self {
+        self.api_version = Some(version.trim_start_matches('v').to_string());
+        self
+    }
+    /// Timeout for receiving a response from the daemon
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// How many times to retry a request that failed to connect to the daemon, f.e. while it's restarting.  
+    /// Requests with a streamed body are never retried. By default requests are not retried.
+    pub fn max_retries(&mut self, retries: u32) -> &mut Self {
+        self.retry.max_retries = retries;
+        self
+    }
+    /// Delay before the first retry, doubled with each next one. Defaults to 100ms.
+    pub fn initial_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.retry.initial_backoff = backoff;
+        self
+    }
+    /// Upper limit of the delay between retries. Defaults to 10s.
+    pub fn max_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.retry.max_backoff = backoff;
+        self
+    }
+    /// Maximum number of idle connections kept open to the daemon for reuse.  
+    /// The client only connects to a single daemon so this is the same as `pool_max_idle_per_host()`.
+    /// It doesn't limit the total number of connections, hyper 0.13 has no such limit and
+    /// concurrent requests open a new connection whenever no idle one is available.
+    pub fn connection_pool_size(&mut self, max: usize) -> &mut Self {
+        self.pool_max_idle_per_host(max)
+    }
+    /// Maximum number of idle connections kept open per host, 0 disables connection reuse.
+    /// Unlimited by default.
+    pub fn pool_max_idle_per_host(&mut self, max: usize) -> &mut Self {
+        self.pool_max_idle = Some(max);
+        self
+    }
+    /// How long idle connections are kept open. Defaults to 90s.
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+    /// Tls configuration used for https connections. Requires `tls` feature.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(&mut self, config: rustls::ClientConfig) -> &mut Self {
+        self.tls_config = Some(config);
+        self
+    }
+    /// Creates a new instance of docker interface.  
+    /// May return an error in case of a missing or bad url or an unsupported scheme.
+    pub fn build(&self) -> Result<Docker, Error> {
+        let url: Uri = match &self.url {
+            Some(url) => url.parse()?,
+            None => return Err(format_err!("docker url was not specified")),
+        };
+        match url.scheme_str() {
+            Some("http") | Some("https") => {}
+            Some(scheme) => return Err(format_err!("unsupported scheme: {}", scheme)),
+            None => return Err(format_err!("docker url is missing a scheme")),
+        }
+        let mut client = hyper::Client::builder();
+        if let Some(timeout) = self.keep_alive_timeout {
+            client.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle {
+            client.pool_max_idle_per_host(max);
+        }
+        Ok(Docker {
+            url,
+            client: client.build(self.connector()),
+            api_version: self.api_version.clone(),
+            timeout: self.timeout,
+            retry: self.retry.clone(),
+        })
+    }
+    #[cfg(feature = "tls")]
+    fn connector(&self) -> Connector {
+        match &self.tls_config {
+            Some(config) => {
+                let mut http = HttpConnector::new();
+                http.enforce_http(false);
+                hyper_rustls::HttpsConnector::from((http, config.clone()))
+            }
+            // Trust native root certificates
+            None => hyper_rustls::HttpsConnector::new(),
+        }
+    }
+    #[cfg(not(feature = "tls"))]
+    fn connector(&self) -> Connector {
+        HttpConnector::new()
+    }
 }
 
 impl Docker {
     /// Creates a new instance of docker interface.  
     /// May return an error in case of a bad url.
     pub fn new(url: &str) -> Result<Self, Error> {
-        Ok(Docker {
-            url: url.parse()?,
-            client: hyper::Client::new(),
-        })
+        DockerBuilder::new().url(url).build()
+    }
+    /// Get a builder for configuring a docker interface
+    pub fn builder() -> DockerBuilder {
+        DockerBuilder::new()
+    }
+    /// Run cmd in a new container created from image and return its output once it exits
+    /// like `docker run --rm` does.  
+    /// The container is removed after its output is collected, also if it failed to start.
+    /// AutoRemove isn't used as the daemon would delete the container before its logs are read.
+    /// A failure to remove the container is only logged so it doesn't hide the output.  
+    /// Returns `ExecOutput` with the exit code of the container as `CmdOut` is the output of an exec.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn run(
+        &self,
+        image: &str,
+        cmd: &[&str],
+        opts: &ContainerBuilderOpts,
+    ) -> Result<ExecOutput, Error> {
+        let mut opts = opts.clone();
+        opts.image(image).cmd(cmd);
+        let container = self.containers().create("", &opts).await?;
+
+        let output = async {
+            container.start().await?;
+            let exit = container.wait(WaitCondition::NotRunning).await?;
+            let mut logs_opts = ContainerLogsOpts::new();
+            logs_opts.stdout(true).stderr(true);
+            let mut logs = Box::pin(container.logs(&logs_opts).await?);
+            let mut stdout = Vec::new();
+            let mut stderr = Vec::new();
+            while let Some(frame) = logs.next().await {
+                match frame? {
+                    LogFrame::Stdout(data) => stdout.extend(data),
+                    LogFrame::Stderr(data) => stderr.extend(data),
+                    LogFrame::Stdin(_) => {}
+                }
+            }
+            Ok(ExecOutput {
+                stdout: String::from_utf8_lossy(&stdout).to_string(),
+                stderr: String::from_utf8_lossy(&stderr).to_string(),
+                exit_code: exit.status_code,
+            })
+        }
+        .await;
+
+        let mut rm_opts = RmContainerOpts::new();
+        rm_opts.force(true);
+        if let Err(e) = container.remove(&rm_opts).await {
+            warn!("failed to remove container {}: {}", container.id, e);
+        }
+        output
     }
     /// Get reference to a specific container interface
-    pub fn container(&self, id: &str) -> Container {
-        Container::new(&self, id)
+    pub fn container<I: Into<ContainerId>>(&self, id: I) -> Container<'_> {
+        Container::new(self, id)
+    }
+    /// Get reference to a specific image interface
+    pub fn image(&self, name: &str) -> Image<'_> {
+        Image::new(self, name)
     }
     /// Get reference to api interface of containers
-    pub fn containers(&self) -> Containers {
-        Containers::new(&self)
+    pub fn containers(&self) -> Containers<'_> {
+        Containers::new(self)
     }
     /// Get reference to api interface of images
-    pub fn images(&self) -> Images {
-        Images::new(&self)
+    pub fn images(&self) -> Images<'_> {
+        Images::new(self)
+    }
+    /// Get reference to a specific network interface
+    pub fn network(&self, id: &str) -> Network<'_> {
+        Network::new(self, id)
     }
     /// Get reference to api interface of networks
-    pub fn networks(&self) -> Networks {
-        Networks::new(&self)
+    pub fn networks(&self) -> Networks<'_> {
+        Networks::new(self)
     }
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, body, headers)))]
     async fn req(
         &self,
         method: Method,
@@ -93,6 +359,10 @@ impl Docker {
         body: Body,
         headers: Option<Vec<(&'static str, String)>>,
     ) -> Result<Response<Body>, Error> {
+        let path = match &self.api_version {
+            Some(version) => format!("/v{}{}", version, path),
+            None => path,
+        };
         let mut uri = self.url.clone().into_parts();
         match query {
             Some(q) => {
@@ -101,25 +371,60 @@ impl Docker {
             None => uri.path_and_query = Some(PathAndQuery::from_str(&path)?),
         }
         let uri = Uri::from_parts(uri)?;
-        let mut req = Request::builder().method(method).uri(uri);
-        if let Some(req_h) = req.headers_mut() {
-            if let Some(h) = headers {
-                h.iter().for_each(|header| {
+        let headers = headers.unwrap_or_default();
+
+        // Only bodies already in memory can be sent again
+        let (mut body, buffered) =
+            if self.retry.max_retries > 0 && HttpBody::size_hint(&body).exact().is_some() {
+                let bytes = to_bytes(body).await?;
+                (Body::from(bytes.clone()), Some(bytes))
+            } else {
+                (body, None)
+            };
+        let mut attempt = 0;
+        let res = loop {
+            let mut req = Request::builder().method(method.clone()).uri(uri.clone());
+            if let Some(req_h) = req.headers_mut() {
+                headers.iter().for_each(|header| {
                     req_h.insert(header.0, HeaderValue::from_str(&header.1).unwrap());
                 });
             }
-        }
-        let req = req.body(body).expect("failed to build a request");
+            let req = req.body(body).expect("failed to build a request");
 
-        trace!("{:?}", req);
-        let res = self.client.request(req).await?;
+            debug!("sending {} {}", req.method(), req.uri());
+            trace!("{:?}", req);
+            match (self.send(req).await, &buffered) {
+                (Err(e), Some(bytes)) if attempt < self.retry.max_retries && is_connect(&e) => {
+                    let backoff = self.retry.backoff(attempt);
+                    warn!(
+                        "failed to connect to docker - {}, retrying in {:?}",
+                        e, backoff
+                    );
+                    tokio::time::delay_for(backoff).await;
+                    attempt += 1;
+                    body = Body::from(bytes.clone());
+                }
+                (res, _) => break res?,
+            }
+        };
 
+        debug!("received {}", res.status());
         trace!("{:?}", res);
         Ok(res)
     }
-    /// Get auth token for authorized operations  
-    /// Returns a base64 encoded json with user data.
-    pub async fn authenticate(&self, opts: &AuthOpts) -> Result<String, Error> {
+    // Sends the request failing after the timeout if one is set
+    async fn send(&self, req: Request<Body>) -> Result<Response<Body>, Error> {
+        Ok(match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.client.request(req))
+                .await
+                .map_err(|_| format_err!("request timed out after {:?}", timeout))??,
+            None => self.client.request(req).await?,
+        })
+    }
+    /// Validate credentials for a registry and get an identity token if available  
+    /// Returns the status message and the token.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, opts)))]
+    pub async fn authenticate(&self, opts: &AuthOpts) -> Result<AuthResponse, Error> {
         let res = self
             .req(
                 Method::POST,
@@ -134,15 +439,13 @@ impl Docker {
         let text = to_bytes(res.into_body()).await?;
         trace!("{}", str::from_utf8(text.as_ref())?);
         match status {
-            200 => {
-                let msg: AuthMsg = serde_json::from_slice(&text)?;
-                Ok(msg.token())
-            }
-            204 => Ok("".to_string()),
-            500 => err_msg!(text, "server error"),
-            _ => err_msg!(text, "unknown error"),
+            200 => Ok(serde_json::from_slice(&text)?),
+            204 => Ok(AuthResponse::default()),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, "unknown error"),
         }
     }
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     pub async fn exec_inspect(&self, exec_id: &str) -> Result<ExecInspect, Error> {
         trace!("{}", exec_id);
         let res = self
@@ -165,33 +468,453 @@ impl Docker {
                 let exec = serde_json::from_slice::<ExecInspect>(&slice)?;
                 Ok(exec)
             }
-            404 => err_msg!(slice, "no such exec instance"),
-            500 => err_msg!(slice, "server error"),
-            _ => err_msg!(slice, "unknown error"),
+            404 => err_msg!(slice, 404, "no such exec instance"),
+            500 => err_msg!(slice, 500, "server error"),
+            _ => err_msg!(slice, status, "unknown error"),
+        }
+    }
+    /// Get system-wide information about the daemon
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn info(&self) -> Result<SystemInfo, Error> {
+        let res = self
+            .req(Method::GET, "/info".into(), None, Body::from(""), None)
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(text.as_ref())?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Get the version of the daemon
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn version(&self) -> Result<VersionInfo, Error> {
+        let res = self
+            .req(Method::GET, "/version".into(), None, Body::from(""), None)
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(text.as_ref())?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Get data usage information of images, containers, volumes and build cache
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn df(&self) -> Result<DiskUsage, Error> {
+        let res = self
+            .req(Method::GET, "/system/df".into(), None, Body::from(""), None)
+            .await?;
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(text.as_ref())?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, ""),
+        }
+    }
+    /// Stream events reported by the daemon in real time  
+    /// The stream ends only if `until` is set in opts.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn events(
+        &self,
+        opts: &EventsOpts,
+    ) -> Result<impl Stream<Item = Result<EventMessage, Error>>, Error> {
+        let res = self
+            .req(
+                Method::GET,
+                "/events".into(),
+                Some(opts.to_query()?),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        let status = res.status().as_u16();
+        match status {
+            200 => Ok(json_stream(res.into_body())),
+            other => {
+                let text = to_bytes(res.into_body()).await?;
+                trace!("{}", str::from_utf8(text.as_ref())?);
+                match other {
+                    400 => err_msg!(text, 400, "bad parameter"),
+                    500 => err_msg!(text, 500, "server error"),
+                    _ => err_msg!(text, other, ""),
+                }
+            }
+        }
+    }
+    /// Remove all stopped containers, unused networks, dangling images and build cache.  
+    /// Volumes are removed only if enabled in opts.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn system_prune(&self, opts: &SystemPruneOpts) -> Result<SystemPruned, Error> {
+        let filters = opts._filters();
+        // Checked before anything is removed, as volumes would be pruned regardless of age
+        if opts._volumes() && filters.contains("until") {
+            return Err(format_err!(
+                "the until filter is not supported when pruning volumes"
+            ));
+        }
+
+        let mut pruned = SystemPruned::default();
+        for (path, keys) in PRUNE_FILTERS {
+            if *path == "/volumes/prune" && !opts._volumes() {
+                continue;
+            }
+            let out = self.prune(path, &filters.only(keys)).await?;
+            pruned
+                .containers_deleted
+                .extend(out.containers_deleted.unwrap_or_default());
+            pruned
+                .images_deleted
+                .extend(out.images_deleted.unwrap_or_default());
+            pruned
+                .networks_deleted
+                .extend(out.networks_deleted.unwrap_or_default());
+            pruned
+                .volumes_deleted
+                .extend(out.volumes_deleted.unwrap_or_default());
+            pruned.space_reclaimed += out.space_reclaimed.unwrap_or_default();
+        }
+        Ok(pruned)
+    }
+    async fn prune(&self, path: &str, filters: &FilterBuilder) -> Result<PruneOut, Error> {
+        let res = self
+            .req(
+                Method::POST,
+                path.into(),
+                Some(filters.to_query()?),
+                Body::from(""),
+                None,
+            )
+            .await?;
+        debug!("{:?}", res);
+        let status = res.status().as_u16();
+        let text = to_bytes(res.into_body()).await?;
+        trace!("{}", str::from_utf8(text.as_ref())?);
+        match status {
+            200 => Ok(serde_json::from_slice(&text)?),
+            500 => err_msg!(text, 500, "server error"),
+            _ => err_msg!(text, status, "unknown error"),
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct Msg {
-    message: String,
+/// Message sent by the daemon along with an error status
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DockerMessage {
+    pub message: String,
+    #[serde(default)]
+    pub cause: Option<String>,
 }
-impl Msg {
-    fn msg(self) -> String {
-        self.message
-    }
+// Whether the error happened while connecting so the request never reached the daemon
+fn is_connect(e: &Error) -> bool {
+    matches!(e.downcast_ref::<hyper::Error>(), Some(e) if e.is_connect())
 }
-#[derive(Serialize, Deserialize)]
-struct AuthMsg {
-    Status: String,
-    IdentityToken: String,
+
+impl DockerMessage {
+    // Message with the cause appended if the daemon sent one
+    pub(crate) fn msg(self) -> String {
+        match self.cause {
+            Some(cause) if !cause.is_empty() => format!("{}: {}", self.message, cause),
+            _ => self.message,
+        }
+    }
 }
-impl AuthMsg {
-    #[allow(dead_code)]
-    fn status(&self) -> String {
-        self.Status.clone()
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn new_validates_scheme() {
+        assert!(Docker::new("http://0.0.0.0:2375").is_ok());
+        assert!(Docker::new("https://0.0.0.0:2376").is_ok());
+        assert_eq!(
+            Docker::new("ftp://0.0.0.0:2375").unwrap_err().to_string(),
+            "unsupported scheme: ftp"
+        );
+        assert_eq!(
+            Docker::new("0.0.0.0:2375").unwrap_err().to_string(),
+            "docker url is missing a scheme"
+        );
+    }
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let retry = Retry {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+        assert_eq!(retry.backoff(0), Duration::from_millis(100));
+        assert_eq!(retry.backoff(1), Duration::from_millis(200));
+        assert_eq!(retry.backoff(2), Duration::from_millis(300));
+        assert_eq!(retry.backoff(40), Duration::from_millis(300));
+    }
+    // Starts a daemon answering requests with the status and body returned by route
+    // for "METHOD /path" and the query. Returns its url and the requests it received.
+    pub(crate) fn mock_daemon<F>(route: F) -> (String, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str, &str) -> (u16, Vec<u8>) + Clone + Send + Sync + 'static,
+    {
+        let (url, requests, _) = mock_daemon_conns(route);
+        (url, requests)
+    }
+    // Same as mock_daemon but also returns the number of connections accepted so far
+    fn mock_daemon_conns<F>(route: F) -> (String, Arc<Mutex<Vec<String>>>, Arc<AtomicUsize>)
+    where
+        F: Fn(&str, &str) -> (u16, Vec<u8>) + Clone + Send + Sync + 'static,
+    {
+        use hyper::service::{make_service_fn, service_fn};
+        use hyper::Server;
+        use std::convert::Infallible;
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let conns = Arc::new(AtomicUsize::new(0));
+        let accepted = conns.clone();
+        let make_svc = make_service_fn(move |_| {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let received = received.clone();
+            let route = route.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let request = format!("{} {}", req.method(), req.uri().path());
+                    received.lock().unwrap().push(request.clone());
+                    let (status, body) = route(&request, req.uri().query().unwrap_or(""));
+                    async move {
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from(body))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        (url, requests, conns)
+    }
+    // Daemon running containers, start and delete answer with the given statuses
+    fn mock_run_daemon(start: u16, delete: u16) -> (String, Arc<Mutex<Vec<String>>>) {
+        mock_daemon(move |request, _| match request {
+            "POST /containers/create" => (201, "{\"Id\":\"abc\"}".into()),
+            "POST /containers/abc/start" => (start, "{\"message\":\"no such file\"}".into()),
+            "POST /containers/abc/wait" => (200, "{\"StatusCode\":3}".into()),
+            "GET /containers/abc/json" => (
+                200,
+                result::tests::container_inspect().to_string().into_bytes(),
+            ),
+            "GET /containers/abc/logs" => (
+                200,
+                b"\x01\x00\x00\x00\x00\x00\x00\x03out\x02\x00\x00\x00\x00\x00\x00\x03err".to_vec(),
+            ),
+            "DELETE /containers/abc" => (delete, "{\"message\":\"removal in progress\"}".into()),
+            _ => (404, Vec::new()),
+        })
     }
-    fn token(&self) -> String {
-        self.IdentityToken.clone()
+    // Makes two sequential requests and returns the number of connections they used
+    async fn connections_used(builder: &mut DockerBuilder, pause: Duration) -> usize {
+        let (url, _, conns) = mock_daemon_conns(|_, _| (500, "{\"message\":\"\"}".into()));
+        let d = builder.url(&url).build().unwrap();
+        d.info().await.unwrap_err();
+        tokio::time::delay_for(pause).await;
+        d.info().await.unwrap_err();
+        conns.load(Ordering::SeqCst)
+    }
+    #[tokio::test]
+    async fn builder_pool_settings_reach_client() {
+        let no_pause = Duration::from_millis(0);
+        assert_eq!(connections_used(&mut Docker::builder(), no_pause).await, 1);
+        assert_eq!(
+            connections_used(Docker::builder().connection_pool_size(0), no_pause).await,
+            2
+        );
+        assert_eq!(
+            connections_used(Docker::builder().pool_max_idle_per_host(0), no_pause).await,
+            2
+        );
+        assert_eq!(
+            connections_used(
+                Docker::builder().keep_alive_timeout(Duration::from_millis(10)),
+                Duration::from_millis(100)
+            )
+            .await,
+            2
+        );
+    }
+    #[tokio::test]
+    async fn run_collects_output_and_removes_container() {
+        let (url, requests) = mock_run_daemon(204, 204);
+        let d = Docker::new(&url).unwrap();
+        let out = d
+            .run(
+                "alpine",
+                &["sh", "-c", "exit 3"],
+                &ContainerBuilderOpts::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(out.stdout, "out");
+        assert_eq!(out.stderr, "err");
+        assert_eq!(out.exit_code, 3);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                "POST /containers/create",
+                "POST /containers/abc/start",
+                "POST /containers/abc/wait",
+                "GET /containers/abc/json",
+                "GET /containers/abc/logs",
+                "DELETE /containers/abc",
+            ]
+        );
+    }
+    #[tokio::test]
+    async fn run_removes_container_that_failed_to_start() {
+        let (url, requests) = mock_run_daemon(500, 204);
+        let d = Docker::new(&url).unwrap();
+        let err = d
+            .run("alpine", &["missing"], &ContainerBuilderOpts::new())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "server error - no such file");
+        assert_eq!(
+            requests.lock().unwrap().last().unwrap(),
+            "DELETE /containers/abc"
+        );
+    }
+    #[tokio::test]
+    async fn run_keeps_output_when_remove_fails() {
+        let (url, _) = mock_run_daemon(204, 409);
+        let d = Docker::new(&url).unwrap();
+        let out = d
+            .run("alpine", &["true"], &ContainerBuilderOpts::new())
+            .await
+            .unwrap();
+        assert_eq!(out.stdout, "out");
+
+        let (url, _) = mock_run_daemon(500, 409);
+        let err = Docker::new(&url)
+            .unwrap()
+            .run("alpine", &["missing"], &ContainerBuilderOpts::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "server error - no such file");
+    }
+    #[tokio::test]
+    async fn system_prune_sends_supported_filters() {
+        let queries = Arc::new(Mutex::new(HashMap::new()));
+        let received = queries.clone();
+        let (url, requests) = mock_daemon(move |request, query| {
+            let query = percent_encoding::percent_decode_str(query).decode_utf8_lossy();
+            let filters: serde_json::Value =
+                serde_json::from_str(query.trim_start_matches("filters=")).unwrap();
+            received
+                .lock()
+                .unwrap()
+                .insert(request.to_string(), filters);
+            let body = match request {
+                "POST /containers/prune" => "{\"ContainersDeleted\":[\"c1\"],\"SpaceReclaimed\":1}",
+                "POST /networks/prune" => "{\"NetworksDeleted\":[\"n1\"]}",
+                "POST /images/prune" => {
+                    "{\"ImagesDeleted\":[{\"Deleted\":\"sha256:i1\"}],\"SpaceReclaimed\":2}"
+                }
+                "POST /volumes/prune" => "{\"VolumesDeleted\":[\"v1\"],\"SpaceReclaimed\":4}",
+                "POST /build/prune" => "{\"CachesDeleted\":[\"b1\"],\"SpaceReclaimed\":8}",
+                _ => return (404, Vec::new()),
+            };
+            (200, body.into())
+        });
+        let d = Docker::new(&url).unwrap();
+        let mut filters = FilterBuilder::new();
+        filters.filter("label", "env=test");
+        let mut opts = SystemPruneOpts::new();
+        opts.volumes(true).filters(&filters);
+        let pruned = d.system_prune(&opts).await.unwrap();
+
+        assert_eq!(pruned.containers_deleted, vec!["c1"]);
+        assert_eq!(pruned.networks_deleted, vec!["n1"]);
+        assert_eq!(pruned.images_deleted[0].deleted, "sha256:i1");
+        assert_eq!(pruned.volumes_deleted, vec!["v1"]);
+        assert_eq!(pruned.space_reclaimed, 15);
+        let queries = queries.lock().unwrap();
+        let label = serde_json::json!({"label": ["env=test"]});
+        assert_eq!(queries["POST /containers/prune"], label);
+        assert_eq!(queries["POST /networks/prune"], label);
+        assert_eq!(queries["POST /images/prune"], label);
+        assert_eq!(queries["POST /volumes/prune"], label);
+        assert_eq!(queries["POST /build/prune"], serde_json::json!({}));
+        assert_eq!(requests.lock().unwrap().len(), 5);
+    }
+    #[tokio::test]
+    async fn system_prune_rejects_until_with_volumes() {
+        let (url, requests) = mock_daemon(|_, _| (200, b"{}".to_vec()));
+        let d = Docker::new(&url).unwrap();
+        let mut filters = FilterBuilder::new();
+        filters.filter("until", "24h");
+        let mut opts = SystemPruneOpts::new();
+        opts.filters(&filters);
+        assert!(d.system_prune(&opts).await.is_ok());
+        assert_eq!(requests.lock().unwrap().len(), 4);
+
+        opts.volumes(true);
+        assert_eq!(
+            d.system_prune(&opts).await.unwrap_err().to_string(),
+            "the until filter is not supported when pruning volumes"
+        );
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+    #[tokio::test]
+    async fn req_retries_until_daemon_is_up() {
+        use hyper::service::{make_service_fn, service_fn};
+        use hyper::Server;
+        use std::convert::Infallible;
+
+        // Reserve a free port and start the daemon on it only after a delay
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        tokio::spawn(async move {
+            tokio::time::delay_for(Duration::from_millis(50)).await;
+            let make_svc = make_service_fn(|_| async {
+                Ok::<_, Infallible>(service_fn(|_| async {
+                    Ok::<_, Infallible>(Response::new(Body::from("")))
+                }))
+            });
+            Server::bind(&addr).serve(make_svc).await.unwrap();
+        });
+
+        let d = Docker::builder()
+            .url(&format!("http://{}", addr))
+            .max_retries(10)
+            .initial_backoff(Duration::from_millis(10))
+            .max_backoff(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        let res = d
+            .req(Method::GET, "/_ping".into(), None, Body::from(""), None)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+
+        let d = Docker::new("http://127.0.0.1:1").unwrap();
+        assert!(d
+            .req(Method::GET, "/_ping".into(), None, Body::from(""), None)
+            .await
+            .is_err());
     }
 }
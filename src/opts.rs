@@ -4,11 +4,20 @@
 //! ```ignore
 //! opts.path("/example/path").no_overwrite(true).copy_uid_gid("false");
 //! ```
+use crate::logging::*;
 use failure::Error;
-use log::*;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde::Serialize;
-use serde_json::Value;
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
+use std::fmt;
+pub use wharf_derive::DockerOpts;
+// Characters left as is in query values, everything else gets percent encoded
+const QUERY_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
 macro_rules! insert {
     ($s:ident, $k:expr, $v:ident) => {
         $s.opts.insert($k, serde_json::to_value($v).unwrap());
@@ -22,69 +31,120 @@ pub trait DockerOpts {
             .opts()
             .iter()
             .map(|(k, v)| {
-                format!(
-                    "{}={}",
-                    k,
-                    serde_json::to_string(&v).unwrap().trim_matches('"')
-                )
+                let v = match v {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                format!("{}={}", k, utf8_percent_encode(&v, QUERY_VALUE))
             })
             .collect();
         Ok(q.join("&"))
     }
 }
-impl DockerOpts for UploadArchiveOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
-    }
+
+/// Filters used by listing endpoints, serialized as a JSON map[string][]string.  
+/// Multiple values of the same key are OR-ed, different keys are AND-ed.
+/// ```ignore
+/// let mut filters = FilterBuilder::new();
+/// filters.filter("driver", "bridge").filter("label", "env=prod");
+/// ```
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct FilterBuilder {
+    filters: HashMap<String, Vec<String>>,
 }
-impl DockerOpts for AuthOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+impl FilterBuilder {
+    pub fn new() -> Self {
+        FilterBuilder::default()
+    }
+    /// Add a filter with key and value
+    pub fn filter(&mut self, key: &str, value: &str) -> &mut Self {
+        self.filters
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_string());
+        self
+    }
+    /// Add all filters from other
+    pub fn extend(&mut self, other: &FilterBuilder) -> &mut Self {
+        other.filters.iter().for_each(|(k, values)| {
+            self.filters
+                .entry(k.clone())
+                .or_default()
+                .extend(values.iter().cloned())
+        });
+        self
     }
-}
-impl DockerOpts for ContainerBuilderOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    // Replaces all values of key with value
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> &mut Self {
+        self.filters
+            .insert(key.to_string(), vec![value.to_string()]);
+        self
     }
-}
-impl DockerOpts for AttachOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    pub(crate) fn remove(&mut self, key: &str) -> &mut Self {
+        self.filters.remove(key);
+        self
     }
-}
-impl DockerOpts for ContainerLogsOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.filters.contains_key(key)
     }
-}
-impl DockerOpts for CreateImageOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    // Filters with one of the keys
+    pub(crate) fn only(&self, keys: &[&str]) -> FilterBuilder {
+        FilterBuilder {
+            filters: self
+                .filters
+                .iter()
+                .filter(|(k, _)| keys.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
     }
-}
-impl DockerOpts for ExecOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    pub(crate) fn to_query(&self) -> Result<String, Error> {
+        let filters = serde_json::to_string(&self.filters)?;
+        Ok(format!(
+            "filters={}",
+            utf8_percent_encode(&filters, QUERY_VALUE)
+        ))
     }
 }
-impl DockerOpts for ListContainersOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
-    }
+
+/// Options for pruning unused containers, networks, images, volumes and build cache
+#[derive(Debug, Default, DockerOpts)]
+pub struct SystemPruneOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+    volumes: bool,
 }
-impl DockerOpts for RmContainerOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+impl SystemPruneOpts {
+    pub fn new() -> Self {
+        SystemPruneOpts::default()
     }
-}
-impl DockerOpts for ImageBuilderOpts {
-    fn opts(&self) -> &HashMap<&'static str, Value> {
-        &self.opts
+    /// Prune volumes too. By default volumes are not removed.
+    pub fn volumes(&mut self, volumes: bool) -> &mut Self {
+        self.volumes = volumes;
+        self
+    }
+    /// Filters to process on the prune list like until or label.  
+    /// Values are added to the filters set before. Every kind of resource is pruned only with
+    /// the filters it supports f.e. label is not applied to build cache, and until can't be
+    /// used together with `volumes(true)`.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/ContainerPrune)
+    pub fn filters(&mut self, filters: &FilterBuilder) -> &mut Self {
+        self.filters.extend(filters);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+    pub(crate) fn _volumes(&self) -> bool {
+        self.volumes
+    }
+    pub(crate) fn _filters(&self) -> &FilterBuilder {
+        &self.filters
     }
 }
 
 /// Options for uploading an archive to a container
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct UploadArchiveOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -110,7 +170,7 @@ impl UploadArchiveOpts {
     }
 }
 /// Options for listing containers
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct ListContainersOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -140,8 +200,487 @@ impl ListContainersOpts {
         self
     }
 }
+/// Options for listing networks
+#[derive(Debug, Default, DockerOpts)]
+pub struct ListNetworksOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+}
+impl ListNetworksOpts {
+    pub fn new() -> Self {
+        ListNetworksOpts::default()
+    }
+    /// Filters to process on the networks list like driver, id, label, name, scope or type.  
+    /// Values are added to the filters set before.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/NetworkList)
+    pub fn filters(&mut self, filters: &FilterBuilder) -> &mut Self {
+        self.filters.extend(filters);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+    /// Only return networks with scope - swarm, global or local.
+    pub fn scope(&mut self, scope: &str) -> &mut Self {
+        self.filters.set("scope", scope);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+}
+/// Options for listing volumes
+#[derive(Debug, Default, DockerOpts)]
+pub struct ListVolumesOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+}
+impl ListVolumesOpts {
+    pub fn new() -> Self {
+        ListVolumesOpts::default()
+    }
+    /// Filters to process on the volumes list like dangling, driver, label or name.  
+    /// Values are added to the filters set before.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/VolumeList)
+    pub fn filters(&mut self, filters: &FilterBuilder) -> &mut Self {
+        self.filters.extend(filters);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+    /// Only return volumes that are (true) or are not (false) in use by a container.
+    pub fn dangling(&mut self, dangling: bool) -> &mut Self {
+        self.filters.set("dangling", &dangling.to_string());
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+}
+/// Options for creating a volume
+#[derive(Debug, Default, DockerOpts)]
+pub struct VolumeCreateOpts {
+    opts: HashMap<&'static str, Value>,
+}
+impl VolumeCreateOpts {
+    pub fn new() -> Self {
+        VolumeCreateOpts::default()
+    }
+    /// The new volume's name. If not specified, Docker generates a name.
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        insert!(self, "Name", name);
+        self
+    }
+    /// Name of the volume driver to use.
+    pub fn driver(&mut self, driver: &str) -> &mut Self {
+        insert!(self, "Driver", driver);
+        self
+    }
+    /// A mapping of driver options and values.  
+    /// These options are passed directly to the driver and are driver specific.
+    pub fn driver_opts(&mut self, opts: &HashMap<&str, &str>) -> &mut Self {
+        insert!(self, "DriverOpts", opts);
+        self
+    }
+    /// User-defined key/value metadata.
+    pub fn labels(&mut self, labels: &HashMap<&str, &str>) -> &mut Self {
+        insert!(self, "Labels", labels);
+        self
+    }
+}
+/// Options for creating a checkpoint of a container
+#[derive(Debug, Default, DockerOpts)]
+pub struct CheckpointCreateOpts {
+    opts: HashMap<&'static str, Value>,
+}
+impl CheckpointCreateOpts {
+    pub fn new() -> Self {
+        CheckpointCreateOpts::default()
+    }
+    /// Name of the checkpoint
+    pub fn checkpoint_id(&mut self, id: &str) -> &mut Self {
+        insert!(self, "CheckpointID", id);
+        self
+    }
+    /// Stop the container after creating the checkpoint
+    pub fn exit(&mut self, exit: bool) -> &mut Self {
+        insert!(self, "Exit", exit);
+        self
+    }
+    /// Directory to store the checkpoint in instead of the default location
+    pub fn checkpoint_dir(&mut self, dir: &str) -> &mut Self {
+        insert!(self, "CheckpointDir", dir);
+        self
+    }
+}
+/// Options for initializing a new swarm
+#[derive(Debug, Default, DockerOpts)]
+pub struct SwarmInitOpts {
+    opts: HashMap<&'static str, Value>,
+}
+impl SwarmInitOpts {
+    pub fn new() -> Self {
+        SwarmInitOpts::default()
+    }
+    /// Listen address used for inter-manager communication in form `192.168.1.1:4567` or an interface followed by a port number like `eth0:4567`.
+    pub fn listen_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "ListenAddr", addr);
+        self
+    }
+    /// Externally reachable address advertised to other nodes, an address or an interface optionally followed by a port number.
+    pub fn advertise_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "AdvertiseAddr", addr);
+        self
+    }
+    /// Address or interface to use for data path traffic
+    pub fn data_path_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "DataPathAddr", addr);
+        self
+    }
+    /// Port number to use for data path traffic, by default 4789
+    pub fn data_path_port(&mut self, port: u32) -> &mut Self {
+        insert!(self, "DataPathPort", port);
+        self
+    }
+    /// Default subnets in CIDR format used for global scope networks
+    pub fn default_addr_pool(&mut self, pools: &[&str]) -> &mut Self {
+        insert!(self, "DefaultAddrPool", pools);
+        self
+    }
+    /// Force creation of a new swarm
+    pub fn force_new_cluster(&mut self, force: bool) -> &mut Self {
+        insert!(self, "ForceNewCluster", force);
+        self
+    }
+    /// Subnet size of the networks created from the default subnet pool
+    pub fn subnet_size(&mut self, size: u32) -> &mut Self {
+        insert!(self, "SubnetSize", size);
+        self
+    }
+}
+/// Options for joining an existing swarm
+#[derive(Debug, Default, DockerOpts)]
+pub struct SwarmJoinOpts {
+    opts: HashMap<&'static str, Value>,
+}
+impl SwarmJoinOpts {
+    pub fn new() -> Self {
+        SwarmJoinOpts::default()
+    }
+    /// Listen address used for inter-manager communication if the node gets promoted to manager
+    pub fn listen_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "ListenAddr", addr);
+        self
+    }
+    /// Externally reachable address advertised to other nodes
+    pub fn advertise_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "AdvertiseAddr", addr);
+        self
+    }
+    /// Address or interface to use for data path traffic
+    pub fn data_path_addr(&mut self, addr: &str) -> &mut Self {
+        insert!(self, "DataPathAddr", addr);
+        self
+    }
+    /// Secret token for joining the swarm
+    pub fn join_token(&mut self, token: &str) -> &mut Self {
+        insert!(self, "JoinToken", token);
+        self
+    }
+    /// Addresses of manager nodes already participating in the swarm
+    pub fn remote_addrs(&mut self, addrs: &[&str]) -> &mut Self {
+        insert!(self, "RemoteAddrs", addrs);
+        self
+    }
+}
+/// Specification of a swarm service used for creating and updating services
+/// # Example
+/// ```ignore
+/// let mut spec = ServiceSpec::new();
+/// spec.name("web").image("nginx:alpine").replicas(3).port(80, 8080);
+/// ```
+#[derive(Debug, Default, DockerOpts)]
+pub struct ServiceSpec {
+    opts: HashMap<&'static str, Value>,
+}
+impl ServiceSpec {
+    pub fn new() -> Self {
+        ServiceSpec::default()
+    }
+    /// Name of the service
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        insert!(self, "Name", name);
+        self
+    }
+    /// User-defined key/value metadata
+    pub fn labels(&mut self, labels: &HashMap<&str, &str>) -> &mut Self {
+        insert!(self, "Labels", labels);
+        self
+    }
+    /// User modifiable task configuration like the container spec, resources or restart policy.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/ServiceCreate)
+    pub fn task_template(&mut self, template: &Value) -> &mut Self {
+        insert!(self, "TaskTemplate", template);
+        self
+    }
+    /// Scheduling mode of the service, either `{"Replicated": {"Replicas": n}}` or `{"Global": {}}`
+    pub fn mode(&mut self, mode: &Value) -> &mut Self {
+        insert!(self, "Mode", mode);
+        self
+    }
+    /// Strategy of updating the service
+    pub fn update_config(&mut self, config: &Value) -> &mut Self {
+        insert!(self, "UpdateConfig", config);
+        self
+    }
+    /// Strategy of rolling back the service after a failed update
+    pub fn rollback_config(&mut self, config: &Value) -> &mut Self {
+        insert!(self, "RollbackConfig", config);
+        self
+    }
+    /// Names or ids of networks to attach the service to
+    pub fn networks(&mut self, networks: &[&str]) -> &mut Self {
+        let networks: Vec<_> = networks.iter().map(|n| json!({ "Target": n })).collect();
+        insert!(self, "Networks", networks);
+        self
+    }
+    /// Properties that can be configured to access and load balance the service
+    pub fn endpoint_spec(&mut self, spec: &Value) -> &mut Self {
+        insert!(self, "EndpointSpec", spec);
+        self
+    }
+    /// Image to run the containers of the service from
+    pub fn image(&mut self, image: &str) -> &mut Self {
+        self.set(&["TaskTemplate", "ContainerSpec", "Image"], json!(image))
+    }
+    /// Run the service in replicated mode with n replicas, replaces the mode set before
+    pub fn replicas(&mut self, n: u64) -> &mut Self {
+        self.set(&["Mode"], json!({ "Replicated": { "Replicas": n } }))
+    }
+    /// Publish target port of the containers as published port on the swarm
+    pub fn port(&mut self, target: u16, published: u16) -> &mut Self {
+        let mut ports = self
+            .opts
+            .get("EndpointSpec")
+            .and_then(|spec| spec.get("Ports"))
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        ports.push(json!({
+            "Protocol": "tcp",
+            "TargetPort": target,
+            "PublishedPort": published,
+        }));
+        self.set(&["EndpointSpec", "Ports"], Value::Array(ports))
+    }
+    // Sets value nested in objects under path creating the missing ones
+    fn set(&mut self, path: &[&'static str], value: Value) -> &mut Self {
+        let (key, path) = path.split_first().expect("path can't be empty");
+        let (last, path) = match path.split_last() {
+            Some(split) => split,
+            None => {
+                self.opts.insert(key, value);
+                return self;
+            }
+        };
+        let mut entry = self.opts.entry(key).or_insert_with(|| json!({}));
+        for key in path {
+            entry = object(entry)
+                .entry(key.to_string())
+                .or_insert_with(|| json!({}));
+        }
+        object(entry).insert(last.to_string(), value);
+        self
+    }
+}
+// Recursively merges objects, any other value replaces the target
+fn merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            for (key, value) in value {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+// Returns the value as an object replacing it if it's not one
+fn object(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = json!({});
+    }
+    value.as_object_mut().unwrap()
+}
+/// Options for filtering events reported by the daemon
+#[derive(Debug, Default, DockerOpts)]
+pub struct EventsOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+}
+impl EventsOpts {
+    pub fn new() -> Self {
+        EventsOpts::default()
+    }
+    /// Show events created since this unix timestamp
+    pub fn since(&mut self, timestamp: i64) -> &mut Self {
+        insert!(self, "since", timestamp);
+        self
+    }
+    /// Show events created until this unix timestamp then stop streaming
+    pub fn until(&mut self, timestamp: i64) -> &mut Self {
+        insert!(self, "until", timestamp);
+        self
+    }
+    /// Only show events of objects of this type like container, image, volume, network, daemon, plugin, node, service, secret or config
+    pub fn type_(&mut self, event_type: &str) -> &mut Self {
+        self.filters.set("type", event_type);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+    /// Filter events by key like container, event, image, label or network.  
+    /// Events matching any of the values of the same key are shown.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/SystemEvents)
+    pub fn filter(&mut self, key: &str, value: &str) -> &mut Self {
+        self.filters.filter(key, value);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+}
+/// Options for creating an image from a container  
+/// All options except `config` are sent as query parameters.
+#[derive(Debug, Default, DockerOpts)]
+pub struct ImageCommitOpts {
+    opts: HashMap<&'static str, Value>,
+    config: Map<String, Value>,
+}
+impl ImageCommitOpts {
+    pub fn new() -> Self {
+        ImageCommitOpts::default()
+    }
+    /// The ID or name of the container to commit
+    pub fn container(&mut self, id: &str) -> &mut Self {
+        insert!(self, "container", id);
+        self
+    }
+    /// Repository name for the created image
+    pub fn repo(&mut self, r: &str) -> &mut Self {
+        insert!(self, "repo", r);
+        self
+    }
+    /// Tag name for the create image
+    pub fn tag(&mut self, t: &str) -> &mut Self {
+        insert!(self, "tag", t);
+        self
+    }
+    /// Commit message
+    pub fn comment(&mut self, c: &str) -> &mut Self {
+        insert!(self, "comment", c);
+        self
+    }
+    /// Author of the image (e.g., John Hannibal Smith <hannibal@a-team.com>)
+    pub fn author(&mut self, a: &str) -> &mut Self {
+        insert!(self, "author", a);
+        self
+    }
+    /// Whether to pause the container before committing
+    pub fn pause(&mut self, pause: bool) -> &mut Self {
+        insert!(self, "pause", pause);
+        self
+    }
+    /// Dockerfile instructions to apply while committing
+    pub fn changes(&mut self, c: &[&str]) -> &mut Self {
+        let changes = c.join("\n");
+        insert!(self, "changes", changes);
+        self
+    }
+    /// Container configuration of the image overriding the configuration of the container
+    pub fn config(&mut self, c: &ContainerBuilderOpts) -> &mut Self {
+        self.config = c.nested();
+        self
+    }
+    pub(crate) fn _config(&self) -> &Map<String, Value> {
+        &self.config
+    }
+}
+/// Options for listing images
+#[derive(Debug, Default, DockerOpts)]
+pub struct ListImagesOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+}
+impl ListImagesOpts {
+    pub fn new() -> Self {
+        ListImagesOpts::default()
+    }
+    /// Show all images. Only images from a final layer (no children) are shown by default.
+    pub fn all(&mut self, all: bool) -> &mut Self {
+        insert!(self, "all", all);
+        self
+    }
+    /// Filters to process on the images list like before, dangling, label, reference or since.  
+    /// Values are added to the filters set before.  
+    /// for more information head to [docker reference](https://docs.docker.com/engine/api/v1.40/#operation/ImageList)
+    pub fn filters(&mut self, filters: &FilterBuilder) -> &mut Self {
+        self.filters.extend(filters);
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+    /// Show digest information as a RepoDigests field on each image.
+    pub fn digests(&mut self, digests: bool) -> &mut Self {
+        insert!(self, "digests", digests);
+        self
+    }
+}
+/// Options for searching images on Docker Hub
+#[derive(Debug, Default, DockerOpts)]
+pub struct SearchOpts {
+    opts: HashMap<&'static str, Value>,
+    filters: FilterBuilder,
+}
+impl SearchOpts {
+    pub fn new() -> Self {
+        SearchOpts::default()
+    }
+    /// Term to search
+    pub fn term(&mut self, term: &str) -> &mut Self {
+        insert!(self, "term", term);
+        self
+    }
+    /// Maximum number of results to return
+    pub fn limit(&mut self, limit: u64) -> &mut Self {
+        insert!(self, "limit", limit);
+        self
+    }
+    /// Only return official images. By default all images are returned.
+    pub fn official_only(&mut self, official: bool) -> &mut Self {
+        self.flag("is-official", official)
+    }
+    /// Only return automated images. By default all images are returned.
+    pub fn automated_only(&mut self, automated: bool) -> &mut Self {
+        self.flag("is-automated", automated)
+    }
+    /// Only return images with at least this many stars
+    pub fn min_stars(&mut self, stars: u32) -> &mut Self {
+        self.filters.set("stars", &stars.to_string());
+        self.update_filters()
+    }
+    // Filters only on true, false means images of both kinds
+    fn flag(&mut self, key: &str, on: bool) -> &mut Self {
+        if on {
+            self.filters.set(key, "true");
+        } else {
+            self.filters.remove(key);
+        }
+        self.update_filters()
+    }
+    fn update_filters(&mut self) -> &mut Self {
+        let filters = &self.filters;
+        insert!(self, "filters", filters);
+        self
+    }
+}
 /// Options for removing a container
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct RmContainerOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -166,7 +705,7 @@ impl RmContainerOpts {
     }
 }
 /// Options for container logs
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct ContainerLogsOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -212,7 +751,7 @@ impl ContainerLogsOpts {
 }
 
 /// Options for building a container
-#[derive(Default)]
+#[derive(Clone, Debug, Default, DockerOpts)]
 pub struct ContainerBuilderOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -220,6 +759,32 @@ impl ContainerBuilderOpts {
     pub fn new() -> Self {
         ContainerBuilderOpts::default()
     }
+    /// Serializes the opts into the body of a create request.
+    /// Keys like `HostConfig.Memory` are nested into `{"HostConfig": {"Memory": ..}}`
+    pub fn to_body(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(&self.to_create_body()?)?)
+    }
+    /// Body of a create request as a json object with nested keys
+    pub fn to_create_body(&self) -> Result<Value, Error> {
+        Ok(Value::Object(self.nested()))
+    }
+    // Builds the nested json object out of opts with keys split on `.`,
+    // more nested keys are merged last so they take precedence.
+    fn nested(&self) -> Map<String, Value> {
+        let mut opts: Vec<_> = self.opts.iter().collect();
+        opts.sort_by_key(|(key, _)| key.matches('.').count());
+        let mut body = Map::new();
+        for (key, value) in opts {
+            let mut path: Vec<&str> = key.split('.').collect();
+            let last = path.pop().unwrap_or_default();
+            let mut entry = &mut body;
+            for key in path {
+                entry = object(entry.entry(key).or_insert_with(|| json!({})));
+            }
+            merge(entry.entry(last).or_insert(Value::Null), value.clone());
+        }
+        body
+    }
     /// Get opts
     /// The hostname to use for the container, as a valid RFC 1123 hostname.
     pub fn hostname<S: Into<String> + Serialize>(&mut self, hostname: S) -> &mut Self {
@@ -334,24 +899,38 @@ impl ContainerBuilderOpts {
     pub fn exposed_ports<S: AsRef<str> + Serialize>(&mut self, ports: &[S]) -> &mut Self {
         let exposed_ports: HashMap<&str, Value> = ports
             .iter()
-            .map(|port| (port.as_ref(), Value::default()))
+            .map(|port| (port.as_ref(), Value::Object(Default::default())))
             .collect();
         debug!("{:?}", exposed_ports);
-        //TODO
-        //figure out what's the difference
-        //insert!(self, "ExposedPorts", exposed_ports);
-        insert!(self, "HostConfig.PortBindings", exposed_ports);
+        insert!(self, "ExposedPorts", exposed_ports);
         self
     }
-    /// A list of mounts in the container in the form:
-    /// "/host/path:/container/path"
-    pub fn volumes<S: AsRef<str> + Serialize>(&mut self, mounts: &[S]) -> &mut Self {
-        let volumes: HashMap<&str, Value> = mounts
+    /// A map of exposed container ports and the host ports they should map to.
+    pub fn port_bindings(&mut self, bindings: &[PortMap]) -> &mut Self {
+        let port_bindings: HashMap<String, Vec<Value>> = bindings
             .iter()
-            .map(|m| (m.as_ref(), Value::default()))
+            .map(|b| {
+                (
+                    format!("{}/{}", b.container_port, b.protocol),
+                    b.host_bindings.iter().map(HostBinding::to_value).collect(),
+                )
+            })
             .collect();
-        debug!("{:?}", volumes);
-        insert!(self, "HostConfig.Binds", volumes);
+        debug!("{:?}", port_bindings);
+        insert!(self, "HostConfig.PortBindings", port_bindings);
+        self
+    }
+    /// A list of mounts in the container in the form:
+    /// "/host/path:/container/path"  
+    /// Use `mounts()` to specify the mount type and its options.
+    pub fn volumes<S: AsRef<str> + Serialize>(&mut self, mounts: &[S]) -> &mut Self {
+        insert!(self, "HostConfig.Binds", mounts);
+        self
+    }
+    /// Mounts of volumes, host paths or tmpfs in the container.  
+    /// Unlike `volumes()` the type and options of each mount can be specified.
+    pub fn mounts(&mut self, mounts: &[Mount]) -> &mut Self {
+        insert!(self, "HostConfig.Mounts", mounts);
         self
     }
     /// User-defined key/value metadata.
@@ -371,10 +950,192 @@ impl ContainerBuilderOpts {
         insert!(self, "HostConfig.NetworkMode", mode);
         self
     }
+    /// A list of additional groups that the container process will run as.
+    pub fn group_add(&mut self, groups: &[&str]) -> &mut Self {
+        insert!(self, "HostConfig.GroupAdd", groups);
+        self
+    }
+    /// Storage driver options for this container, in the form {"size": "120G"}.
+    pub fn storage_opt(&mut self, opts: &HashMap<&str, &str>) -> &mut Self {
+        insert!(self, "HostConfig.StorageOpt", opts);
+        self
+    }
+    /// Isolation technology of the container - default, process or hyperv (Windows only).
+    pub fn isolation(&mut self, mode: &str) -> &mut Self {
+        insert!(self, "HostConfig.Isolation", mode);
+        self
+    }
+    /// User namespace to use for the container - `host` disables the user namespace remapping
+    /// configured on the daemon.
+    pub fn user_ns_mode(&mut self, mode: &str) -> &mut Self {
+        insert!(self, "HostConfig.UsernsMode", mode);
+        self
+    }
+    /// UTS namespace to use for the container - `host` shares the hostname of the host machine.
+    pub fn uts_mode(&mut self, mode: &str) -> &mut Self {
+        insert!(self, "HostConfig.UTSMode", mode);
+        self
+    }
+    /// Driver that this container uses to mount volumes.
+    pub fn volume_driver(&mut self, driver: &str) -> &mut Self {
+        insert!(self, "HostConfig.VolumeDriver", driver);
+        self
+    }
+    /// Length of a CPU real-time period in microseconds.
+    pub fn cpu_realtime_period(&mut self, microseconds: i64) -> &mut Self {
+        insert!(self, "HostConfig.CpuRealtimePeriod", microseconds);
+        self
+    }
+    /// Length of a CPU real-time runtime in microseconds.
+    pub fn cpu_realtime_runtime(&mut self, microseconds: i64) -> &mut Self {
+        insert!(self, "HostConfig.CpuRealtimeRuntime", microseconds);
+        self
+    }
+}
+
+/// State of a container to wait for in `Container::wait()`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum WaitCondition {
+    /// Wait until the container is not running, returns immediately if it's already stopped
+    #[default]
+    NotRunning,
+    /// Wait until the container exits the next time
+    NextExit,
+    /// Wait until the container is removed
+    Removed,
+}
+impl fmt::Display for WaitCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let condition = match self {
+            WaitCondition::NotRunning => "not-running",
+            WaitCondition::NextExit => "next-exit",
+            WaitCondition::Removed => "removed",
+        };
+        write!(f, "{}", condition)
+    }
+}
+
+/// Mapping of a container port to host ports used by `ContainerBuilderOpts::port_bindings()`
+/// ```ignore
+/// let http = PortMap {
+///     container_port: 80,
+///     protocol: "tcp",
+///     host_bindings: vec![HostBinding { host_ip: None, host_port: Some(8080) }],
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct PortMap<'a> {
+    pub container_port: u16,
+    /// tcp, udp or sctp
+    pub protocol: &'a str,
+    pub host_bindings: Vec<HostBinding<'a>>,
+}
+
+/// Host ip and port a container port is bound to.  
+/// If not specified the host ip defaults to all interfaces and the host port is chosen by docker.
+#[derive(Clone, Debug, Default)]
+pub struct HostBinding<'a> {
+    pub host_ip: Option<&'a str>,
+    pub host_port: Option<u16>,
+}
+impl<'a> HostBinding<'a> {
+    fn to_value(&self) -> Value {
+        json!({
+            "HostIp": self.host_ip.unwrap_or_default(),
+            "HostPort": self.host_port.map(|p| p.to_string()).unwrap_or_default(),
+        })
+    }
+}
+
+/// Mount added to a container with `ContainerBuilderOpts::mounts()`
+/// ```ignore
+/// let data = Mount {
+///     type_: MountType::Volume,
+///     source: Some("data".into()),
+///     target: "/var/lib/data".into(),
+///     read_only: false,
+///     bind_options: None,
+///     volume_options: None,
+///     tmpfs_options: None,
+/// };
+/// ```
+#[derive(Clone, Debug, Serialize)]
+pub struct Mount {
+    #[serde(rename = "Type")]
+    pub type_: MountType,
+    /// Host path of a bind mount or the name of a volume
+    #[serde(rename = "Source", skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Path in the container
+    #[serde(rename = "Target")]
+    pub target: String,
+    #[serde(rename = "ReadOnly")]
+    pub read_only: bool,
+    #[serde(rename = "BindOptions", skip_serializing_if = "Option::is_none")]
+    pub bind_options: Option<BindOptions>,
+    #[serde(rename = "VolumeOptions", skip_serializing_if = "Option::is_none")]
+    pub volume_options: Option<VolumeOptions>,
+    #[serde(rename = "TmpfsOptions", skip_serializing_if = "Option::is_none")]
+    pub tmpfs_options: Option<TmpfsOptions>,
+}
+
+/// Type of a mount
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountType {
+    /// A file or directory of the host
+    Bind,
+    /// A volume managed by docker, created if it doesn't exist
+    Volume,
+    /// A filesystem kept in memory
+    Tmpfs,
+    /// A named pipe of the host (Windows only)
+    Npipe,
+}
+
+/// Options of a bind mount
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BindOptions {
+    /// private, rprivate, shared, rshared, slave or rslave
+    #[serde(rename = "Propagation", skip_serializing_if = "Option::is_none")]
+    pub propagation: Option<String>,
+}
+
+/// Options of a volume mount
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VolumeOptions {
+    /// Don't populate the volume with data of the target path in the image
+    #[serde(rename = "NoCopy")]
+    pub no_copy: bool,
+    #[serde(rename = "Labels")]
+    pub labels: HashMap<String, String>,
+    /// Driver used to create the volume if it doesn't exist
+    #[serde(rename = "DriverConfig", skip_serializing_if = "Option::is_none")]
+    pub driver_config: Option<VolumeDriverConfig>,
+}
+
+/// Driver of a volume and its options
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VolumeDriverConfig {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Options")]
+    pub options: HashMap<String, String>,
+}
+
+/// Options of a tmpfs mount
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TmpfsOptions {
+    /// Size of the mount in bytes, unlimited by default
+    #[serde(rename = "SizeBytes", skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<i64>,
+    /// Permission mode of the mount as an integer f.e. 0o1777
+    #[serde(rename = "Mode", skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
 }
 
 /// Options for attaching to a container
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct AttachOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -409,10 +1170,16 @@ impl AttachOpts {
         insert!(self, "logs", logs);
         self
     }
+    /// Override the key sequence for detaching a container.  
+    /// Format is a single character [a-Z] or ctrl-<value> where <value> is one of: a-z, @, ^, [, , or _.
+    pub fn detach_keys(&mut self, keys: &str) -> &mut Self {
+        insert!(self, "detachKeys", keys);
+        self
+    }
 }
 
 /// Options for building an image
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct ImageBuilderOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -527,10 +1294,47 @@ impl ImageBuilderOpts {
         insert!(self, "target", t);
         self
     }
+    /// Build the image with BuildKit instead of the classic builder.  
+    /// BuildKit progress is sent as `BuildOutput` messages with id `moby.buildkit.trace`.
+    pub fn use_buildkit(&mut self, enabled: bool) -> &mut Self {
+        let version = if enabled { "2" } else { "1" };
+        insert!(self, "version", version);
+        self
+    }
+    pub(crate) fn _buildkit(&self) -> bool {
+        self.opts.get("version") == Some(&json!("2"))
+    }
+    /// Export the build result with BuildKit in the same format as `docker build --output`,
+    /// f.e. `type=image,name=registry.example.com/app:v1,push=true`. Enables BuildKit.  
+    /// Only the image and moby exporters are supported, building fails with any other type.
+    /// The local and tar exporters stream the result back over a BuildKit session
+    /// which is not available here, a plain path like `./output` is a local export.  
+    /// The daemon accepts a single output so calling it again replaces the previous one.
+    pub fn output(&mut self, outputs: &str) -> &mut Self {
+        let mut type_ = "local";
+        let mut attrs = Map::new();
+        for pair in outputs.split(',').filter(|pair| !pair.is_empty()) {
+            match pair.find('=') {
+                Some(i) if &pair[..i] == "type" => type_ = &pair[i + 1..],
+                Some(i) => {
+                    attrs.insert(pair[..i].to_string(), json!(&pair[i + 1..]));
+                }
+                None => {
+                    attrs.insert("dest".to_string(), json!(pair));
+                }
+            }
+        }
+        self.opts
+            .insert("outputs", json!([{"Type": type_, "Attrs": attrs}]));
+        self.use_buildkit(true)
+    }
+    pub(crate) fn _output_type(&self) -> Option<&str> {
+        self.opts.get("outputs")?.get(0)?.get("Type")?.as_str()
+    }
 }
 
 /// Options for creating image
-#[derive(Default)]
+#[derive(Debug, Default, DockerOpts)]
 pub struct CreateImageOpts {
     opts: HashMap<&'static str, Value>,
     auth: AuthOpts,
@@ -591,12 +1395,35 @@ impl CreateImageOpts {
     }
 }
 
+/// Options for pushing an image to a registry
+#[derive(Debug, Default, DockerOpts)]
+pub struct PushImageOpts {
+    opts: HashMap<&'static str, Value>,
+}
+impl PushImageOpts {
+    pub fn new() -> Self {
+        PushImageOpts::default()
+    }
+    /// The tag to associate with the image on the registry.
+    pub fn tag(&mut self, t: &str) -> &mut Self {
+        insert!(self, "tag", t);
+        self
+    }
+}
+
 /// Options for authentication
-#[derive(Clone, Default)]
+#[derive(Clone, Default, DockerOpts)]
 pub struct AuthOpts {
     opts: HashMap<&'static str, Value>,
 }
 
+// Credentials are left out so they don't end up in logs
+impl fmt::Debug for AuthOpts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<_> = self.opts.keys().collect();
+        f.debug_struct("AuthOpts").field("opts", &keys).finish()
+    }
+}
 impl AuthOpts {
     pub fn new() -> Self {
         AuthOpts::default()
@@ -622,8 +1449,17 @@ impl AuthOpts {
     }
 }
 
+// Serializes credentials of multiple registries for the X-Registry-Config header
+pub(crate) fn registry_config(configs: &HashMap<&str, &AuthOpts>) -> Result<String, Error> {
+    let configs: HashMap<&str, &HashMap<&'static str, Value>> = configs
+        .iter()
+        .map(|(registry, auth)| (*registry, auth.opts()))
+        .collect();
+    Ok(base64::encode(&serde_json::to_string(&configs)?))
+}
+
 /// Options for executing commands
-#[derive(Clone, Default, Serialize)]
+#[derive(Clone, Debug, Default, Serialize, DockerOpts)]
 pub struct ExecOpts {
     opts: HashMap<&'static str, Value>,
 }
@@ -649,7 +1485,7 @@ impl ExecOpts {
     }
     /// Detach from the command.
     pub fn detach(&mut self, detach: bool) -> &mut Self {
-        insert!(self, "detach", detach);
+        insert!(self, "Detach", detach);
         self
     }
     /// Allocate a pseudo-TTY.
@@ -683,6 +1519,12 @@ impl ExecOpts {
         insert!(self, "WorkingDir", dir);
         self
     }
+    /// Initial size of the TTY in characters
+    pub fn console_size(&mut self, height: u32, width: u32) -> &mut Self {
+        let size = [height, width];
+        insert!(self, "ConsoleSize", size);
+        self
+    }
     pub(crate) fn _tty(&self) -> bool {
         if let Some(tty) = self.opts.get("Tty") {
             serde_json::from_value(tty.clone()).unwrap()
@@ -691,15 +1533,36 @@ impl ExecOpts {
         }
     }
     pub(crate) fn _detach(&self) -> bool {
-        if let Some(detach) = self.opts.get("detach") {
+        if let Some(detach) = self.opts.get("Detach") {
             serde_json::from_value(detach.clone()).unwrap()
         } else {
-            true
+            false
         }
     }
+    pub(crate) fn _start_body(&self) -> ExecStartBody {
+        ExecStartBody {
+            detach: self._detach(),
+            tty: self._tty(),
+        }
+    }
+}
+
+// Body of a request starting an exec instance.
+// The daemon expects only these fields so the rest of ExecOpts is left out.
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct ExecStartBody {
+    #[serde(rename = "Detach")]
+    pub(crate) detach: bool,
+    #[serde(rename = "Tty")]
+    pub(crate) tty: bool,
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::needless_borrows_for_generic_args,
+    clippy::let_unit_value,
+    clippy::useless_conversion
+)]
 mod tests {
     use super::*;
     use serde_json::json;
@@ -748,6 +1611,392 @@ mod tests {
             .collect()
     }
     #[test]
+    fn list_networks_opts_work() {
+        let query = json!({
+            "filters": {
+                "driver": ["bridge"],
+                "scope": ["local"]
+            }
+        });
+
+        let mut filters = FilterBuilder::new();
+        filters.filter("driver", "bridge");
+        let mut opts = ListNetworksOpts::new();
+        opts.filters(&filters).scope("swarm").scope("local");
+
+        opts.opts
+            .iter()
+            .map(|(k, v)| {
+                let val = query.get(k);
+                assert!(val.is_some());
+                assert_eq!(val.unwrap(), v);
+            })
+            .collect()
+    }
+    #[test]
+    fn list_images_opts_work() {
+        let query = json!({
+            "all": true,
+            "digests": false,
+            "filters": {
+                "dangling": ["true"],
+                "label": ["a", "b"]
+            }
+        });
+
+        let mut filters = FilterBuilder::new();
+        filters.filter("dangling", "true").filter("label", "a");
+        let mut labels = FilterBuilder::new();
+        labels.filter("label", "b");
+        let mut opts = ListImagesOpts::new();
+        opts.all(true)
+            .digests(false)
+            .filters(&filters)
+            .filters(&labels);
+
+        opts.opts
+            .iter()
+            .map(|(k, v)| {
+                let val = query.get(k);
+                assert!(val.is_some());
+                assert_eq!(val.unwrap(), v);
+            })
+            .collect()
+    }
+    #[test]
+    fn list_volumes_opts_work() {
+        let query = json!({
+            "filters": {
+                "driver": ["local"],
+                "dangling": ["true"]
+            }
+        });
+
+        let mut filters = FilterBuilder::new();
+        filters.filter("driver", "local");
+        let mut opts = ListVolumesOpts::new();
+        opts.filters(&filters).dangling(false).dangling(true);
+
+        opts.opts
+            .iter()
+            .map(|(k, v)| {
+                let val = query.get(k);
+                assert!(val.is_some());
+                assert_eq!(val.unwrap(), v);
+            })
+            .collect()
+    }
+    #[test]
+    fn volume_create_opts_work() {
+        let body = json!({
+            "Name": "tardis",
+            "Driver": "custom",
+            "DriverOpts": {
+                "device": "tmpfs"
+            },
+            "Labels": {
+                "com.example.some-label": "some-value"
+            }
+        });
+
+        let mut driver_opts = HashMap::new();
+        driver_opts.insert("device", "tmpfs");
+        let mut labels = HashMap::new();
+        labels.insert("com.example.some-label", "some-value");
+
+        let mut opts = VolumeCreateOpts::new();
+        opts.name("tardis")
+            .driver("custom")
+            .driver_opts(&driver_opts)
+            .labels(&labels);
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), body);
+    }
+    #[test]
+    fn checkpoint_create_opts_work() {
+        let body = json!({
+            "CheckpointID": "cp1",
+            "Exit": true,
+            "CheckpointDir": "/tmp/checkpoints"
+        });
+
+        let mut opts = CheckpointCreateOpts::new();
+        opts.checkpoint_id("cp1")
+            .exit(true)
+            .checkpoint_dir("/tmp/checkpoints");
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), body);
+    }
+    #[test]
+    fn swarm_init_opts_work() {
+        let body = json!({
+            "ListenAddr": "0.0.0.0:2377",
+            "AdvertiseAddr": "192.168.1.1:2377",
+            "DataPathAddr": "192.168.1.1",
+            "DataPathPort": 4789,
+            "DefaultAddrPool": ["10.10.0.0/8", "20.20.0.0/8"],
+            "ForceNewCluster": false,
+            "SubnetSize": 24
+        });
+
+        let mut opts = SwarmInitOpts::new();
+        opts.listen_addr("0.0.0.0:2377")
+            .advertise_addr("192.168.1.1:2377")
+            .data_path_addr("192.168.1.1")
+            .data_path_port(4789)
+            .default_addr_pool(&["10.10.0.0/8", "20.20.0.0/8"])
+            .force_new_cluster(false)
+            .subnet_size(24);
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), body);
+    }
+    #[test]
+    fn swarm_join_opts_work() {
+        let body = json!({
+            "ListenAddr": "0.0.0.0:2377",
+            "AdvertiseAddr": "192.168.1.1:2377",
+            "DataPathAddr": "192.168.1.1",
+            "RemoteAddrs": ["node1:2377"],
+            "JoinToken": "SWMTKN-1-3pu6hszjas19xyp7ghgosyx9k8atbfcr8p2is99znpy26u2lkl-7p73s1dx5in4tatdymyhg9hu2"
+        });
+
+        let mut opts = SwarmJoinOpts::new();
+        opts.listen_addr("0.0.0.0:2377")
+            .advertise_addr("192.168.1.1:2377")
+            .data_path_addr("192.168.1.1")
+            .remote_addrs(&["node1:2377"])
+            .join_token("SWMTKN-1-3pu6hszjas19xyp7ghgosyx9k8atbfcr8p2is99znpy26u2lkl-7p73s1dx5in4tatdymyhg9hu2");
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), body);
+    }
+    #[test]
+    fn service_spec_works() {
+        let body = json!({
+            "Name": "web",
+            "Labels": {"com.example.vendor": "Acme"},
+            "TaskTemplate": {
+                "ContainerSpec": {"Image": "nginx:alpine", "User": "33"},
+                "RestartPolicy": {"Condition": "on-failure"}
+            },
+            "Mode": {"Replicated": {"Replicas": 3}},
+            "UpdateConfig": {"Parallelism": 2},
+            "RollbackConfig": {"Parallelism": 1},
+            "Networks": [{"Target": "overlay1"}],
+            "EndpointSpec": {
+                "Mode": "vip",
+                "Ports": [
+                    {"Protocol": "tcp", "TargetPort": 80, "PublishedPort": 8080},
+                    {"Protocol": "tcp", "TargetPort": 443, "PublishedPort": 8443}
+                ]
+            }
+        });
+
+        let mut labels = HashMap::new();
+        labels.insert("com.example.vendor", "Acme");
+
+        let mut spec = ServiceSpec::new();
+        spec.name("web")
+            .labels(&labels)
+            .task_template(&json!({
+                "ContainerSpec": {"User": "33"},
+                "RestartPolicy": {"Condition": "on-failure"}
+            }))
+            .image("nginx:alpine")
+            .mode(&json!({"Global": {}}))
+            .replicas(3)
+            .update_config(&json!({"Parallelism": 2}))
+            .rollback_config(&json!({"Parallelism": 1}))
+            .networks(&["overlay1"])
+            .endpoint_spec(&json!({"Mode": "vip"}))
+            .port(80, 8080)
+            .port(443, 8443);
+
+        assert_eq!(serde_json::to_value(&spec.opts).unwrap(), body);
+    }
+    #[test]
+    fn events_opts_work() {
+        let query = json!({
+            "since": 1461943101,
+            "until": 1461943201,
+            "filters": {
+                "type": ["container"],
+                "event": ["start", "stop"]
+            }
+        });
+
+        let mut opts = EventsOpts::new();
+        opts.since(1461943101)
+            .until(1461943201)
+            .type_("image")
+            .type_("container")
+            .filter("event", "start")
+            .filter("event", "stop");
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), query);
+    }
+    #[test]
+    fn container_builder_opts_to_body_nests_keys() {
+        let mut opts = ContainerBuilderOpts::new();
+        opts.image("ubuntu")
+            .memory(1000000)
+            .network_mode("bridge")
+            .group_add(&["docker"]);
+        let expected = json!({
+            "Image": "ubuntu",
+            "HostConfig": {
+                "Memory": 1000000,
+                "NetworkMode": "bridge",
+                "GroupAdd": ["docker"]
+            }
+        });
+
+        let body: Value = serde_json::from_str(&opts.to_body().unwrap()).unwrap();
+        assert_eq!(body, expected);
+        assert_eq!(opts.to_create_body().unwrap(), expected);
+    }
+    #[test]
+    fn container_builder_opts_mounts() {
+        let mut opts = ContainerBuilderOpts::new();
+        opts.mounts(&[
+            Mount {
+                type_: MountType::Bind,
+                source: Some("/home/user/data".into()),
+                target: "/data".into(),
+                read_only: true,
+                bind_options: Some(BindOptions {
+                    propagation: Some("rprivate".into()),
+                }),
+                volume_options: None,
+                tmpfs_options: None,
+            },
+            Mount {
+                type_: MountType::Tmpfs,
+                source: None,
+                target: "/run".into(),
+                read_only: false,
+                bind_options: None,
+                volume_options: None,
+                tmpfs_options: Some(TmpfsOptions {
+                    size_bytes: Some(65536),
+                    mode: None,
+                }),
+            },
+        ]);
+
+        assert_eq!(
+            opts.to_create_body().unwrap(),
+            json!({
+                "HostConfig": {
+                    "Mounts": [
+                        {
+                            "Type": "bind",
+                            "Source": "/home/user/data",
+                            "Target": "/data",
+                            "ReadOnly": true,
+                            "BindOptions": {"Propagation": "rprivate"}
+                        },
+                        {
+                            "Type": "tmpfs",
+                            "Target": "/run",
+                            "ReadOnly": false,
+                            "TmpfsOptions": {"SizeBytes": 65536}
+                        }
+                    ]
+                }
+            })
+        );
+    }
+    #[test]
+    fn to_create_body_merges_objects() {
+        let mut opts = ContainerBuilderOpts::new();
+        opts.memory(1000000);
+        opts.opts
+            .insert("HostConfig", json!({"Privileged": true, "Memory": 0}));
+
+        assert_eq!(
+            opts.to_create_body().unwrap(),
+            json!({"HostConfig": {"Privileged": true, "Memory": 1000000}})
+        );
+    }
+    #[test]
+    fn push_image_opts_work() {
+        let mut opts = PushImageOpts::new();
+        opts.tag("v1");
+
+        assert_eq!(opts.to_query().unwrap(), "tag=v1");
+    }
+    #[test]
+    fn image_commit_opts_work() {
+        let query = json!({
+            "container": "boring_johnny",
+            "repo": "user/image",
+            "tag": "v1",
+            "comment": "initial commit",
+            "author": "John Hannibal Smith <hannibal@a-team.com>",
+            "pause": false,
+            "changes": "CMD [\"/bin/sh\"]\nENV DEBUG=true"
+        });
+
+        let mut config = ContainerBuilderOpts::new();
+        config.env(&["DEBUG=true"]);
+        let mut opts = ImageCommitOpts::new();
+        opts.container("boring_johnny")
+            .repo("user/image")
+            .tag("v1")
+            .comment("initial commit")
+            .author("John Hannibal Smith <hannibal@a-team.com>")
+            .pause(false)
+            .changes(&["CMD [\"/bin/sh\"]", "ENV DEBUG=true"])
+            .config(&config);
+
+        assert_eq!(serde_json::to_value(&opts.opts).unwrap(), query);
+        assert_eq!(
+            serde_json::to_value(opts._config()).unwrap(),
+            json!({"Env": ["DEBUG=true"]})
+        );
+    }
+    #[test]
+    fn search_opts_work() {
+        let query = json!({
+            "term": "alpine",
+            "limit": 5,
+            "filters": {
+                "is-official": ["true"],
+                "stars": ["100"]
+            }
+        });
+
+        let mut opts = SearchOpts::new();
+        opts.term("alpine")
+            .limit(5)
+            .official_only(true)
+            .automated_only(true)
+            .automated_only(false)
+            .min_stars(50)
+            .min_stars(100);
+
+        opts.opts
+            .iter()
+            .map(|(k, v)| {
+                let val = query.get(k);
+                assert!(val.is_some());
+                assert_eq!(val.unwrap(), v);
+            })
+            .collect()
+    }
+    #[test]
+    fn to_query_encodes_values() {
+        let mut filters = FilterBuilder::new();
+        filters.filter("scope", "swarm");
+        let mut opts = ListNetworksOpts::new();
+        opts.filters(&filters);
+
+        assert_eq!(
+            opts.to_query().unwrap(),
+            "filters=%7B%22scope%22%3A%5B%22swarm%22%5D%7D"
+        );
+    }
+    #[test]
     fn rm_container_opts_work() {
         let mut query = HashMap::new();
         query.insert("volumes", serde_json::to_value(&true).unwrap());
@@ -770,6 +2019,8 @@ mod tests {
     fn container_builder_opts_work() {
         let mut labels = HashMap::new();
         labels.insert("test", "label");
+        let mut storage_opt = HashMap::new();
+        storage_opt.insert("size", "120G");
 
         let body = json!({
             "Hostname": "test_hostname",
@@ -795,15 +2046,27 @@ mod tests {
             "Labels": {
                 "test": "label"
             },
-            "HostConfig.PortBindings": {
-                "22/tcp": null,
-                "443/tcp": null
+            "ExposedPorts": {
+                "22/tcp": {},
+                "443/tcp": {}
             },
-            "HostConfig.Binds": {
-                "/home/host/path:/home/container/path": null
+            "HostConfig.PortBindings": {
+                "22/tcp": [{"HostIp": "", "HostPort": ""}],
+                "443/tcp": [{"HostIp": "127.0.0.1", "HostPort": "8443"}]
             },
+            "HostConfig.Binds": ["/home/host/path:/home/container/path"],
             "HostConfig.Memory": 1000000,
             "HostConfig.NetworkMode": "bridge",
+            "HostConfig.GroupAdd": ["docker"],
+            "HostConfig.StorageOpt": {
+                "size": "120G"
+            },
+            "HostConfig.Isolation": "hyperv",
+            "HostConfig.UsernsMode": "host",
+            "HostConfig.UTSMode": "host",
+            "HostConfig.VolumeDriver": "convoy",
+            "HostConfig.CpuRealtimePeriod": 1000000,
+            "HostConfig.CpuRealtimeRuntime": 950000,
         });
 
         let mut opts = ContainerBuilderOpts::new();
@@ -829,9 +2092,32 @@ mod tests {
             .shell(&[""])
             .labels(&labels)
             .exposed_ports(&["22/tcp", "443/tcp"])
+            .port_bindings(&[
+                PortMap {
+                    container_port: 22,
+                    protocol: "tcp",
+                    host_bindings: vec![HostBinding::default()],
+                },
+                PortMap {
+                    container_port: 443,
+                    protocol: "tcp",
+                    host_bindings: vec![HostBinding {
+                        host_ip: Some("127.0.0.1"),
+                        host_port: Some(8443),
+                    }],
+                },
+            ])
             .volumes(&["/home/host/path:/home/container/path"])
             .memory(1000000)
-            .network_mode("bridge");
+            .network_mode("bridge")
+            .group_add(&["docker"])
+            .storage_opt(&storage_opt)
+            .isolation("hyperv")
+            .user_ns_mode("host")
+            .uts_mode("host")
+            .volume_driver("convoy")
+            .cpu_realtime_period(1000000)
+            .cpu_realtime_runtime(950000);
 
         opts.opts
             .iter()
@@ -843,6 +2129,78 @@ mod tests {
             .collect()
     }
     #[test]
+    fn attach_opts_work() {
+        let query = json!({
+            "stream": true,
+            "stdin": true,
+            "stdout": true,
+            "stderr": false,
+            "logs": false,
+            "detachKeys": "ctrl-p,ctrl-q",
+        });
+
+        let mut opts = AttachOpts::new();
+        opts.stream(true)
+            .stdin(true)
+            .stdout(true)
+            .stderr(false)
+            .logs(false)
+            .detach_keys("ctrl-p,ctrl-q");
+
+        opts.opts
+            .iter()
+            .map(|(k, v)| {
+                let val = query.get(k);
+                assert!(val.is_some());
+                assert_eq!(val.unwrap(), v);
+            })
+            .collect()
+    }
+    #[test]
+    fn attach_opts_to_query() {
+        let mut opts = AttachOpts::new();
+        opts.stream(true)
+            .stdin(false)
+            .stdout(true)
+            .stderr(true)
+            .logs(true)
+            .detach_keys("ctrl-p,ctrl-q");
+
+        let query = opts.to_query().unwrap();
+        let mut params: Vec<&str> = query.split('&').collect();
+        params.sort();
+        assert_eq!(
+            params,
+            vec![
+                "detachKeys=ctrl-p%2Cctrl-q",
+                "logs=true",
+                "stderr=true",
+                "stdin=false",
+                "stdout=true",
+                "stream=true"
+            ]
+        );
+    }
+    #[test]
+    fn registry_config_maps_registries() {
+        let mut auth = AuthOpts::new();
+        auth.username("user").password("pass");
+        let mut configs = HashMap::new();
+        configs.insert("registry.example.com", &auth);
+
+        let decoded = base64::decode(&registry_config(&configs).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&decoded).unwrap(),
+            json!({"registry.example.com": {"username": "user", "password": "pass"}})
+        );
+    }
+    #[test]
+    fn wait_condition_displays_api_strings() {
+        assert_eq!(WaitCondition::default().to_string(), "not-running");
+        assert_eq!(WaitCondition::NextExit.to_string(), "next-exit");
+        assert_eq!(WaitCondition::Removed.to_string(), "removed");
+    }
+    #[test]
     fn auth_opts_work() {
         let opts_j = json!({
             "username": "user",
@@ -915,7 +2273,8 @@ mod tests {
             "Cmd": ["/bin/echo", "this definitely works"],
             "Privileged": false,
             "User": "test_user",
-            "WorkingDir": "/tmp/dir"
+            "WorkingDir": "/tmp/dir",
+            "ConsoleSize": [24, 80]
         });
 
         let mut opts = ExecOpts::new();
@@ -927,7 +2286,8 @@ mod tests {
             .cmd(&["/bin/echo".into(), "this definitely works".into()])
             .privileged(false)
             .user("test_user")
-            .working_dir("/tmp/dir");
+            .working_dir("/tmp/dir")
+            .console_size(24, 80);
 
         opts.opts
             .iter()
@@ -939,6 +2299,22 @@ mod tests {
             .collect()
     }
     #[test]
+    fn exec_start_body_has_only_detach_and_tty() {
+        let mut opts = ExecOpts::new();
+        opts.cmd(&["/bin/ls"]).user("test_user").tty(true);
+
+        assert_eq!(
+            serde_json::to_value(opts._start_body()).unwrap(),
+            json!({"Detach": false, "Tty": true})
+        );
+
+        opts.detach(true);
+        assert_eq!(
+            serde_json::to_value(opts._start_body()).unwrap(),
+            json!({"Detach": true, "Tty": true})
+        );
+    }
+    #[test]
     fn image_builder_opts_work() {
         let mut labels = HashMap::new();
         labels.insert("test", "label");
@@ -971,6 +2347,7 @@ mod tests {
             "networkmode": "bridge",
             "platform": "",
             "target": "",
+            "version": "2",
         });
 
         let mut opts = ImageBuilderOpts::new();
@@ -993,7 +2370,8 @@ mod tests {
             .labels(&labels)
             .network_mode("bridge")
             .platform("")
-            .target("");
+            .target("")
+            .use_buildkit(true);
 
         opts.opts
             .iter()
@@ -1005,4 +2383,35 @@ mod tests {
             })
             .collect()
     }
+    #[test]
+    fn image_builder_opts_output_parses_exports() {
+        let mut opts = ImageBuilderOpts::new();
+        opts.output("out");
+        assert_eq!(
+            opts.opts.get("outputs"),
+            Some(&json!([{"Type": "local", "Attrs": {"dest": "out"}}]))
+        );
+        assert!(opts._buildkit());
+
+        opts.output("type=image,name=app:v1,push=true");
+        assert_eq!(
+            opts.opts.get("outputs"),
+            Some(&json!([
+                {"Type": "image", "Attrs": {"name": "app:v1", "push": "true"}}
+            ]))
+        );
+        assert_eq!(opts._output_type(), Some("image"));
+    }
+    #[test]
+    fn use_buildkit_sets_version() {
+        let mut opts = ImageBuilderOpts::new();
+        assert!(!opts._buildkit());
+
+        opts.use_buildkit(true);
+        assert_eq!(opts.opts.get("version"), Some(&json!("2")));
+        assert!(opts._buildkit());
+
+        opts.use_buildkit(false);
+        assert!(!opts._buildkit());
+    }
 }
@@ -0,0 +1,41 @@
+//! Derive macros for wharf.
+extern crate proc_macro;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+/// Implements `wharf::opts::DockerOpts` for a struct that keeps its options
+/// in an `opts: HashMap<&'static str, Value>` field.
+#[proc_macro_derive(DockerOpts)]
+pub fn derive_docker_opts(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let has_opts = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .any(|f| f.ident.as_ref().map(|i| i == "opts").unwrap_or(false)),
+            _ => false,
+        },
+        _ => false,
+    };
+    if !has_opts {
+        return Error::new_spanned(
+            &input.ident,
+            "DockerOpts can only be derived for structs with an `opts` field",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let expanded = quote! {
+        impl #impl_generics ::wharf::opts::DockerOpts for #name #ty_generics #where_clause {
+            fn opts(&self) -> &::std::collections::HashMap<&'static str, ::serde_json::Value> {
+                &self.opts
+            }
+        }
+    };
+    expanded.into()
+}